@@ -0,0 +1,512 @@
+use std::sync::Arc;
+
+use crate::{
+    PI,
+    color::Color,
+    distribution::CosineHemisphere,
+    hittable::{HitRecord, Hittable, HittableList},
+    integrator::{Integrator, IntegratorConfig, sky_box},
+    interval::Interval,
+    ray::Ray,
+    sampler::Sampler,
+    spectrum,
+    vector::{Onb, Point, Vector},
+};
+
+enum VertexKind {
+    Camera,
+    /// A point sampled on a light, facing outwards.
+    Light(HitRecord),
+    /// A surface hit, with the ray that reached it.
+    Surface(HitRecord, Box<Ray>),
+}
+
+/// A vertex of a camera or light subpath. Densities are per unit area, `pdf_fwd`
+/// for the subpath reaching this vertex and `pdf_rev` for the opposite subpath.
+struct Vertex {
+    kind: VertexKind,
+    point: Point,
+    /// Throughput of the subpath up to, but excluding, this vertex.
+    beta: Color,
+    /// The scattering here cannot be evaluated, so connections skip it.
+    delta: bool,
+    pdf_fwd: f64,
+    pdf_rev: f64,
+}
+
+impl Vertex {
+    fn hit_record(&self) -> Option<&HitRecord> {
+        match &self.kind {
+            VertexKind::Camera => None,
+            VertexKind::Light(hit_record) | VertexKind::Surface(hit_record, _) => Some(hit_record),
+        }
+    }
+
+    /// Converts a solid-angle density of sampling `next` from this vertex into
+    /// a density per unit area at `next`.
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let w: Vector = next.point - self.point;
+        let distance_2: f64 = w.norm_squared();
+        if distance_2 == 0. {
+            return 0.;
+        }
+        let cosine: f64 = next.hit_record().map_or(1., |hit_record| {
+            hit_record.normal().dot(&w).abs() / distance_2.sqrt()
+        });
+        pdf * cosine / distance_2
+    }
+
+    /// Density per unit area of this vertex scattering towards `next`, having
+    /// been reached from `prev`.
+    fn pdf(&self, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+        match (&self.kind, prev) {
+            (VertexKind::Light(_), _) => self.pdf_light(next),
+            (VertexKind::Surface(hit_record, ray), Some(prev)) => {
+                let ray_in: Ray = ray.scattered(prev.point, self.point - prev.point);
+                let pdf: f64 =
+                    hit_record
+                        .material()
+                        .pdf(&ray_in, hit_record, &(next.point - self.point));
+                self.convert_density(pdf, next)
+            }
+            _ => 0.,
+        }
+    }
+
+    /// Density per unit area of this vertex emitting towards `next` as a
+    /// diffuse light.
+    fn pdf_light(&self, next: &Vertex) -> f64 {
+        let Some(hit_record) = self.hit_record() else {
+            return 0.;
+        };
+        let w: Vector = (next.point - self.point).normalize();
+        self.convert_density(hit_record.normal().dot(&w).max(0.) / PI, next)
+    }
+
+    /// Scattered light towards `next`, times the cosine there. On light
+    /// subpaths, `adjoint` corrects for the flow running against the ray.
+    fn eval(&self, next: &Vertex, adjoint: bool) -> Color {
+        match &self.kind {
+            VertexKind::Surface(hit_record, ray) => {
+                let direction: Vector = next.point - self.point;
+                let scattered: Color = spectrum::project(
+                    hit_record.material().eval(ray, hit_record, &direction),
+                    ray.wavelength(),
+                );
+                if adjoint {
+                    scattered * shading_correction(hit_record, &-ray.direction(), &direction)
+                } else {
+                    scattered
+                }
+            }
+            _ => Color::zeros(),
+        }
+    }
+}
+
+/// Factor turning scattering from `to_prev` to `to_next` along a light subpath
+/// into its adjoint, which differs from it wherever the shading normal leans
+/// away from the geometric one.
+fn shading_correction(hit_record: &HitRecord, to_prev: &Vector, to_next: &Vector) -> f64 {
+    let shading_normal: Vector = hit_record.shading_normal();
+    let normal: Vector = hit_record.normal();
+    let denominator: f64 = to_prev.dot(&normal).abs() * to_next.dot(&shading_normal).abs();
+    if denominator == 0. {
+        return 0.;
+    }
+    to_prev.dot(&shading_normal).abs() * to_next.dot(&normal).abs() / denominator
+}
+
+/// Bidirectional path tracer. Every camera ray is extended into a camera
+/// subpath, a light subpath is started from the scene lights, and every pair
+/// of their prefixes is connected, combining the resulting strategies with
+/// multiple importance sampling under the balance heuristic.
+///
+/// The radiance of a camera ray only covers strategies with at least one
+/// surface vertex on the camera side; light paths connecting straight to the
+/// lens would land in other pixels. Lights are the list given to the config
+/// with `with_lights`, and their objects must also be part of the world.
+pub struct BdptIntegrator {
+    config: IntegratorConfig,
+}
+
+impl BdptIntegrator {
+    pub fn new(config: IntegratorConfig) -> Self {
+        BdptIntegrator { config }
+    }
+
+    /// Extends `path` by following `ray` until it holds `max_vertices` vertices
+    /// or the walk ends, and returns the sky seen if the ray escapes. `adjoint`
+    /// marks a walk carrying light rather than importance.
+    #[allow(clippy::too_many_arguments)]
+    fn random_walk(
+        &self,
+        mut ray: Ray,
+        mut beta: Color,
+        pdf: f64,
+        world: &HittableList,
+        time: Interval,
+        sampler: &mut dyn Sampler,
+        path: &mut Vec<Vertex>,
+        max_vertices: usize,
+        adjoint: bool,
+    ) -> Color {
+        let wavelength: Option<f64> = ray.wavelength();
+        let mut pdf_fwd: f64 = pdf;
+
+        while path.len() < max_vertices {
+            let Some(hit_record) = world.hit(&ray, time) else {
+                return beta.component_mul(&spectrum::project(sky_box(&ray), wavelength));
+            };
+
+            let transmittance: Color = ray
+                .media()
+                .transmittance(hit_record.t() * ray.direction().norm());
+            beta.component_mul_assign(&spectrum::project(transmittance, wavelength));
+
+            let material = hit_record.material();
+            let scattering = material.scatter(&ray, &hit_record, sampler);
+
+            let mut vertex = Vertex {
+                kind: VertexKind::Surface(hit_record.clone(), Box::new(ray.clone())),
+                point: hit_record.point(),
+                beta,
+                delta: false,
+                pdf_fwd: 0.,
+                pdf_rev: 0.,
+            };
+            let prev: &Vertex = path.last().unwrap();
+            vertex.pdf_fwd = prev.convert_density(pdf_fwd, &vertex);
+            path.push(vertex);
+
+            let Some(scattering) = scattering.filter(|_| path.len() < max_vertices) else {
+                break;
+            };
+
+            let direction: Vector = scattering.ray().direction();
+            let pdf_rev: f64 = match scattering.pdf() {
+                Some(pdf) => {
+                    pdf_fwd = pdf;
+                    let ray_rev: Ray = ray.scattered(hit_record.point() + direction, -direction);
+                    material.pdf(&ray_rev, &hit_record, &-ray.direction())
+                }
+                None => {
+                    path.last_mut().unwrap().delta = true;
+                    pdf_fwd = 0.;
+                    0.
+                }
+            };
+            beta.component_mul_assign(&spectrum::project(scattering.attenuation(), wavelength));
+            if adjoint {
+                beta *= shading_correction(&hit_record, &-ray.direction(), &direction);
+            }
+
+            let [.., prev, current] = path.as_mut_slice() else {
+                unreachable!()
+            };
+            prev.pdf_rev = current.convert_density(pdf_rev, prev);
+
+            ray = scattering.ray().clone();
+        }
+
+        Color::zeros()
+    }
+
+    fn light_path(
+        &self,
+        lights: &HittableList,
+        world: &HittableList,
+        time: Interval,
+        wavelength: Option<f64>,
+        sampler: &mut dyn Sampler,
+    ) -> Vec<Vertex> {
+        // Connections need at least one camera vertex past the lens.
+        let max_vertices: usize = (self.config.max_depth() - 1).max(0) as usize;
+        let Some((hit_record, pdf_position)) = lights.sample_surface(sampler) else {
+            return Vec::new();
+        };
+
+        let local: Vector = CosineHemisphere.warp(sampler.get_2d());
+        let pdf_direction: f64 = CosineHemisphere.pdf(&local);
+        if max_vertices == 0 || pdf_position <= 0. || pdf_direction <= 0. {
+            return Vec::new();
+        }
+        let direction: Vector = Onb::new(&hit_record.normal()).to_world(&local);
+        let ray: Ray = Ray::new(hit_record.point(), direction).with_wavelength(wavelength);
+        let emitted: Color = spectrum::project(
+            hit_record.material().emitted(
+                &ray.scattered(hit_record.point() + direction, -direction),
+                &hit_record,
+            ),
+            wavelength,
+        );
+        if emitted.max() <= 0. {
+            return Vec::new();
+        }
+
+        let mut path: Vec<Vertex> = vec![Vertex {
+            point: hit_record.point(),
+            kind: VertexKind::Light(hit_record),
+            beta: emitted / pdf_position,
+            delta: false,
+            pdf_fwd: pdf_position,
+            pdf_rev: 0.,
+        }];
+        let beta: Color = emitted * local.z / (pdf_position * pdf_direction);
+        self.random_walk(
+            ray,
+            beta,
+            pdf_direction,
+            world,
+            time,
+            sampler,
+            &mut path,
+            max_vertices,
+            true,
+        );
+        path
+    }
+
+    /// Contribution of the path made of the first `s` light vertices and the
+    /// first `t` camera vertices.
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        &self,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+        lights: Option<&HittableList>,
+        world: &HittableList,
+        time: Interval,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let pt: &Vertex = &camera_path[t - 1];
+        let VertexKind::Surface(hit_record, ray) = &pt.kind else {
+            return Color::zeros();
+        };
+        let wavelength: Option<f64> = ray.wavelength();
+
+        if s == 0 {
+            let emitted: Color =
+                spectrum::project(hit_record.material().emitted(ray, hit_record), wavelength);
+            if emitted.max() <= 0. {
+                return Color::zeros();
+            }
+            let weight: f64 = self.mis_weight(light_path, camera_path, None, s, t, lights);
+            return pt.beta.component_mul(&emitted) * weight;
+        }
+        if pt.delta {
+            return Color::zeros();
+        }
+
+        // A fresh point on a light replaces the first light vertex.
+        let sampled: Option<Vertex> = match s {
+            1 => {
+                let Some((light_hit, pdf)) =
+                    lights.and_then(|lights| lights.sample_surface(sampler))
+                else {
+                    return Color::zeros();
+                };
+                let w: Vector = pt.point - light_hit.point();
+                if pdf <= 0. || light_hit.normal().dot(&w) <= 0. {
+                    return Color::zeros();
+                }
+                let emitted: Color = spectrum::project(
+                    light_hit
+                        .material()
+                        .emitted(&ray.scattered(pt.point, -w), &light_hit),
+                    wavelength,
+                );
+                Some(Vertex {
+                    point: light_hit.point(),
+                    kind: VertexKind::Light(light_hit),
+                    beta: emitted / pdf,
+                    delta: false,
+                    pdf_fwd: pdf,
+                    pdf_rev: 0.,
+                })
+            }
+            _ => None,
+        };
+        let qs: &Vertex = sampled.as_ref().unwrap_or(&light_path[s - 1]);
+        if qs.delta {
+            return Color::zeros();
+        }
+
+        let w: Vector = qs.point - pt.point;
+        let distance: f64 = w.norm();
+        let qs_scattered: Color = match &qs.kind {
+            VertexKind::Light(light_hit) => {
+                Color::repeat(light_hit.normal().dot(&-w).max(0.) / distance)
+            }
+            _ => qs.eval(pt, true),
+        };
+        let contribution: Color = qs
+            .beta
+            .component_mul(&qs_scattered)
+            .component_mul(&pt.eval(qs, false))
+            .component_mul(&pt.beta)
+            / distance.powi(2);
+        if contribution.max() <= 0. {
+            return Color::zeros();
+        }
+
+        let shadow: Ray = ray.scattered(pt.point, w / distance);
+        if world
+            .hit(&shadow, Interval::new(time.min(), distance - time.min()))
+            .is_some()
+        {
+            return Color::zeros();
+        }
+        let transmittance: Color =
+            spectrum::project(shadow.media().transmittance(distance), wavelength);
+
+        let weight: f64 = self.mis_weight(light_path, camera_path, sampled.as_ref(), s, t, lights);
+        contribution.component_mul(&transmittance) * weight
+    }
+
+    /// Balance heuristic weight of connecting `s` light and `t` camera vertices,
+    /// found by walking the ratios of the densities of the neighbouring
+    /// strategies that would have produced the same path.
+    fn mis_weight(
+        &self,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        sampled: Option<&Vertex>,
+        s: usize,
+        t: usize,
+        lights: Option<&HittableList>,
+    ) -> f64 {
+        if s + t == 2 {
+            return 1.;
+        }
+
+        let pt: &Vertex = &camera_path[t - 1];
+        let pt_minus: &Vertex = &camera_path[t - 2];
+        let qs: Option<&Vertex> = match s {
+            0 => None,
+            1 => sampled,
+            _ => Some(&light_path[s - 1]),
+        };
+        let qs_minus: Option<&Vertex> = s.checked_sub(2).map(|i| &light_path[i]);
+
+        // Reverse densities at the end points change with the strategy.
+        let pt_rev: f64 = match qs {
+            Some(qs) => qs.pdf(qs_minus, pt),
+            None => lights.map_or(0., |lights| lights.surface_pdf(&pt.point)),
+        };
+        if s == 0 && pt_rev <= 0. {
+            // Emitters outside the light list are only ever hit.
+            return 1.;
+        }
+        let pt_minus_rev: f64 = match qs {
+            Some(qs) => pt.pdf(Some(qs), pt_minus),
+            None => pt.pdf_light(pt_minus),
+        };
+        let qs_rev: f64 = qs.map_or(0., |qs| pt.pdf(Some(pt_minus), qs));
+        let qs_minus_rev: f64 = match (qs, qs_minus) {
+            (Some(qs), Some(qs_minus)) => qs.pdf(Some(pt), qs_minus),
+            _ => 0.,
+        };
+
+        let remap = |pdf: f64| if pdf != 0. { pdf } else { 1. };
+        let mut sum: f64 = 0.;
+
+        let mut ratio: f64 = 1.;
+        for i in (2..t).rev() {
+            let pdf_rev: f64 = match t - 1 - i {
+                0 => pt_rev,
+                1 => pt_minus_rev,
+                _ => camera_path[i].pdf_rev,
+            };
+            ratio *= remap(pdf_rev) / remap(camera_path[i].pdf_fwd);
+            let delta: bool = i != t - 1 && camera_path[i].delta;
+            if !delta && !camera_path[i - 1].delta {
+                sum += ratio;
+            }
+        }
+
+        let mut ratio: f64 = 1.;
+        for i in (0..s).rev() {
+            let (vertex, pdf_rev): (&Vertex, f64) = match s - 1 - i {
+                0 => (qs.unwrap(), qs_rev),
+                1 => (&light_path[i], qs_minus_rev),
+                _ => (&light_path[i], light_path[i].pdf_rev),
+            };
+            ratio *= remap(pdf_rev) / remap(vertex.pdf_fwd);
+            let delta: bool = i != s - 1 && vertex.delta;
+            let delta_prev: bool = i > 0 && light_path[i - 1].delta;
+            if !delta && !delta_prev {
+                sum += ratio;
+            }
+        }
+
+        1. / (1. + sum)
+    }
+}
+
+impl Integrator for BdptIntegrator {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        time: Interval,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let max_depth: usize = self.config.max_depth().max(0) as usize;
+        let lights: Option<&HittableList> = self
+            .config
+            .lights()
+            .map(Arc::as_ref)
+            .filter(|lights| !lights.is_empty());
+
+        let mut camera_path: Vec<Vertex> = vec![Vertex {
+            kind: VertexKind::Camera,
+            point: ray.origin(),
+            beta: Color::repeat(1.),
+            delta: false,
+            pdf_fwd: 1.,
+            pdf_rev: 0.,
+        }];
+        let mut radiance: Color = self.random_walk(
+            ray.clone(),
+            Color::repeat(1.),
+            1.,
+            world,
+            time,
+            sampler,
+            &mut camera_path,
+            max_depth + 1,
+            false,
+        );
+
+        let light_path: Vec<Vertex> = match lights {
+            Some(lights) => self.light_path(lights, world, time, ray.wavelength(), sampler),
+            None => Vec::new(),
+        };
+        let max_s: usize = match lights {
+            Some(_) => light_path.len().max(1),
+            None => 0,
+        };
+
+        // Paths have at most `max_depth` vertices besides the camera, as in the
+        // unidirectional integrators.
+        for t in 2..=camera_path.len() {
+            for s in 0..=max_s.min(max_depth + 1 - t) {
+                radiance += self.connect(
+                    &light_path,
+                    &camera_path,
+                    s,
+                    t,
+                    lights,
+                    world,
+                    time,
+                    sampler,
+                );
+            }
+        }
+
+        self.config.clamped(radiance)
+    }
+}
@@ -1,15 +1,74 @@
-use rand::Rng;
-use std::sync::Arc;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::{
     color::{Color, Color3},
-    distribution::UniformUnitVec3D,
-    hittable::{Hittable, HittableList},
+    distribution::ConcentricDisk,
+    hittable::HittableList,
+    integrator::Integrator,
     interval::Interval,
-    ray::Ray,
-    vector::{Point, R3, Vector},
+    ray::{Ray, RayDifferentials},
+    sampler::{IndependentSampler, Sampler},
+    spectrum,
+    vector::{Point, Vector},
 };
 
+/// Stops sampling a pixel once the standard error of its mean luminance falls
+/// below `threshold` times that mean, after at least `min_samples` samples.
+#[derive(Clone, Copy)]
+pub struct AdaptiveSampling {
+    min_samples: u32,
+    threshold: f64,
+}
+
+impl AdaptiveSampling {
+    pub fn new(min_samples: u32, threshold: f64) -> Self {
+        AdaptiveSampling {
+            min_samples: min_samples.max(2),
+            threshold,
+        }
+    }
+
+    fn converged(&self, estimate: &PixelEstimate) -> bool {
+        if estimate.count < self.min_samples {
+            return false;
+        }
+        let error: f64 = (estimate.variance() / f64::from(estimate.count)).sqrt();
+        error <= self.threshold * estimate.luminance_mean.max(1e-2)
+    }
+}
+
+/// Running mean and variance of a pixel, updated with Welford's algorithm.
+#[derive(Default)]
+struct PixelEstimate {
+    count: u32,
+    mean: Color,
+    luminance_mean: f64,
+    luminance_m2: f64,
+}
+
+impl PixelEstimate {
+    fn push(&mut self, color: Color) {
+        self.count += 1;
+        let n: f64 = f64::from(self.count);
+        self.mean += (color - self.mean) / n;
+
+        let luminance: f64 = 0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z;
+        let delta: f64 = luminance - self.luminance_mean;
+        self.luminance_mean += delta / n;
+        self.luminance_m2 += delta * (luminance - self.luminance_mean);
+    }
+
+    fn variance(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        self.luminance_m2 / f64::from(self.count - 1)
+    }
+}
+
 pub struct Camera {
     image_width: i32,
     image_height: i32,
@@ -27,10 +86,19 @@ pub struct Camera {
 
     samples_per_pixel: i32,
     pixel_samples_scale: f64,
-    max_depth: i32,
+
+    wavelengths_per_sample: Option<u32>,
+
+    sampler: Box<dyn Sampler>,
+    seed: u64,
+
+    adaptive_sampling: Option<AdaptiveSampling>,
+    sample_heatmap: Option<PathBuf>,
+    render_passes: Vec<(PathBuf, Arc<dyn Integrator>)>,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         image_width: i32,
         aspect_ratio: f64,
@@ -41,7 +109,6 @@ impl Camera {
         defocus_angle: f64,
         focus_dist: f64,
         samples_per_pixel: i32,
-        max_depth: i32,
     ) -> Self {
         let image_height: i32 = {
             let image_height: i32 = (f64::from(image_width) / aspect_ratio).trunc() as i32;
@@ -88,120 +155,288 @@ impl Camera {
             defocus_disk_v,
             samples_per_pixel,
             pixel_samples_scale,
-            max_depth,
+            wavelengths_per_sample: None,
+            sampler: Box::new(IndependentSampler::new(samples_per_pixel as u32)),
+            seed: 0,
+            adaptive_sampling: None,
+            sample_heatmap: None,
+            render_passes: Vec::new(),
         }
     }
 
-    pub fn get_ray(&self, i: i32, j: i32, offset: (f64, f64)) -> Ray {
+    /// Renders spectrally: every camera sample traces `wavelengths` stratified
+    /// wavelengths and accumulates them through CIE XYZ into the output colour.
+    pub fn with_spectral_sampling(mut self, wavelengths: u32) -> Self {
+        self.wavelengths_per_sample = Some(wavelengths.max(1));
+        self
+    }
+
+    /// Replaces the default independent sampler; the camera then takes as many
+    /// samples per pixel as the sampler was configured for.
+    pub fn with_sampler(mut self, mut sampler: Box<dyn Sampler>) -> Self {
+        self.samples_per_pixel = sampler.samples_per_pixel().max(1) as i32;
+        self.pixel_samples_scale = 1.0 / f64::from(self.samples_per_pixel);
+        sampler.set_seed(self.seed);
+        self.sampler = sampler;
+        self
+    }
+
+    /// Renders with the same seed are identical, bit for bit.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.sampler.set_seed(seed);
+        self
+    }
+
+    /// Lets every pixel stop between `min_samples` and the samples per pixel
+    /// once its estimate is within `threshold` relative error.
+    pub fn with_adaptive_sampling(mut self, min_samples: u32, threshold: f64) -> Self {
+        self.adaptive_sampling = Some(AdaptiveSampling::new(min_samples, threshold));
+        self
+    }
+
+    /// Writes the number of samples each pixel took to `path` as a greyscale
+    /// PPM, scaled so that white is the full samples per pixel.
+    pub fn with_sample_heatmap(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sample_heatmap = Some(path.into());
+        self
+    }
+
+    /// Also renders the scene with `integrator`, e.g. ambient occlusion, and
+    /// writes it to `path` as a PPM. The pass traces the same camera rays as
+    /// the main image, reusing its sample counts when sampling is adaptive.
+    pub fn with_render_pass(
+        mut self,
+        path: impl Into<PathBuf>,
+        integrator: Arc<dyn Integrator>,
+    ) -> Self {
+        self.render_passes.push((path.into(), integrator));
+        self
+    }
+
+    pub fn get_ray(&self, i: i32, j: i32, sampler: &mut dyn Sampler) -> Ray {
+        let offset: (f64, f64) = sampler.get_pixel_2d();
         let pixel_center: Point = self.pixel_00_pos
-            + (f64::from(i) + offset.0) * self.pixel_delta_u
-            + (f64::from(j) + offset.1) * self.pixel_delta_v;
+            + (f64::from(i) + offset.0 - 0.5) * self.pixel_delta_u
+            + (f64::from(j) + offset.1 - 0.5) * self.pixel_delta_v;
 
         let ray_origin: Point = if self.defocus_angle <= 0. {
             self.camera_center
         } else {
-            let offset = UniformUnitVec3D::random_in_unit_disk();
+            let offset = ConcentricDisk.warp(sampler.get_2d());
             self.camera_center + offset.0 * self.defocus_disk_u + offset.1 * self.defocus_disk_v
         };
 
         let ray_direction: Vector = pixel_center - ray_origin;
 
-        Ray::new(self.camera_center, ray_direction)
+        let ray: Ray = Ray::new(self.camera_center, ray_direction);
+
+        // Neighbouring pixels, brought closer as more samples share the pixel.
+        let differentials = RayDifferentials::new(
+            ray.origin(),
+            ray_direction + self.pixel_delta_u,
+            ray.origin(),
+            ray_direction + self.pixel_delta_v,
+        )
+        .scaled(&ray, self.pixel_samples_scale.sqrt().max(0.125));
+
+        ray.with_differentials(Some(differentials))
+    }
+
+    /// Radiance carried by one camera ray through pixel `(i, j)`, drawing all its
+    /// dimensions from the current sample of `sampler`.
+    pub fn sample(
+        &self,
+        i: i32,
+        j: i32,
+        world: &HittableList,
+        integrator: &dyn Integrator,
+        time: Interval,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let ray: Ray = self.get_ray(i, j, sampler);
+        let Some(wavelengths) = self.wavelengths_per_sample else {
+            return integrator.radiance(&ray, world, time, sampler);
+        };
+
+        let u: f64 = sampler.get_1d();
+        (0..wavelengths)
+            .map(|k| {
+                let wavelength: f64 =
+                    spectrum::sample_wavelength((f64::from(k) + u) / f64::from(wavelengths));
+                let ray: Ray = ray.clone().with_wavelength(Some(wavelength));
+                let radiance: f64 = integrator.radiance(&ray, world, time, sampler).x;
+                spectrum::spectral_to_rgb(radiance, wavelength, spectrum::wavelength_pdf())
+            })
+            .sum::<Color>()
+            / f64::from(wavelengths)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn is_spectral(&self) -> bool {
+        self.wavelengths_per_sample.is_some()
+    }
+
+    pub fn image_width(&self) -> i32 {
+        self.image_width
+    }
+
+    pub fn image_height(&self) -> i32 {
+        self.image_height
     }
 
-    pub fn render(
+    /// Linear pixel colours, row by row from the top left. Each pixel sums its
+    /// samples in order on a single thread, so the result does not depend on
+    /// how rayon schedules the work.
+    pub fn render_image(
         &self,
-        world: HittableList,
-        colorizer: fn(&Ray, Arc<HittableList>, Interval, i32) -> Color,
+        world: &HittableList,
+        integrator: &dyn Integrator,
         time: Interval,
-    ) {
-        use crate::distribution::UniformOffset2D;
+    ) -> Vec<Color> {
+        self.render_image_with_counts(world, integrator, time).0
+    }
+
+    /// Like `render_image`, also returning how many samples each pixel took.
+    pub fn render_image_with_counts(
+        &self,
+        world: &HittableList,
+        integrator: &dyn Integrator,
+        time: Interval,
+    ) -> (Vec<Color>, Vec<u32>) {
+        self.render_pixels(world, integrator, time, None)
+    }
+
+    /// Renders `integrator` through the same camera samples as an image that
+    /// took `counts` samples per pixel, as returned by `render_image_with_counts`.
+    pub fn render_pass(
+        &self,
+        world: &HittableList,
+        integrator: &dyn Integrator,
+        time: Interval,
+        counts: &[u32],
+    ) -> Vec<Color> {
+        self.render_pixels(world, integrator, time, Some(counts)).0
+    }
+
+    /// Samples every pixel `counts` times, or until adaptive sampling stops it.
+    fn render_pixels(
+        &self,
+        world: &HittableList,
+        integrator: &dyn Integrator,
+        time: Interval,
+        counts: Option<&[u32]>,
+    ) -> (Vec<Color>, Vec<u32>) {
         use indicatif::{ParallelProgressIterator, ProgressStyle};
         use itertools::Itertools;
-        use log::info;
         use rayon::prelude::*;
-        use std::time::{Duration, Instant};
-
-        env_logger::init();
 
         let style = ProgressStyle::with_template(
             "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {human_pos}/{human_len} ({eta})",
         ).unwrap().progress_chars("#>-");
 
-        info!("Rendering started.");
-
-        println!("P3");
-        println!("{} {}", self.image_width, self.image_height);
-        println!("255");
-
-        let start = Instant::now();
+        integrator.prepare(world, time, self.is_spectral(), self.seed);
 
         let pixels: Vec<(i32, i32)> = (0..self.image_height)
             .cartesian_product(0..self.image_width)
             .collect();
 
-        let mut buf: Vec<Color> =
+        let mut buf: Vec<(Color, u32)> =
             Vec::with_capacity((self.image_width * self.image_height) as usize);
 
-        let r_dist = UniformOffset2D::new(-0.5, 0.5);
-
-        let world = Arc::new(world);
-
         pixels
             .into_par_iter()
             .progress_with_style(style)
-            .map(|(j, i)| {
-                (0..self.samples_per_pixel)
-                    .into_par_iter()
-                    .map_init(rand::rng, |rng, _| rng.sample(&r_dist))
-                    .map(|offset| {
-                        let ray: Ray = self.get_ray(i, j, offset);
-                        colorizer(&ray, world.clone(), time, self.max_depth)
-                    })
-                    .sum::<Color>()
-                    * self.pixel_samples_scale
-            })
+            .map_init(
+                || self.sampler.boxed_clone(),
+                |sampler, (j, i)| {
+                    let mut estimate = PixelEstimate::default();
+                    let samples: u32 = counts.map_or(self.samples_per_pixel as u32, |counts| {
+                        counts[(j * self.image_width + i) as usize]
+                    });
+                    for index in 0..samples {
+                        sampler.start_pixel_sample((i, j), index);
+                        estimate.push(self.sample(i, j, world, integrator, time, sampler.as_mut()));
+                        if counts.is_none()
+                            && self
+                                .adaptive_sampling
+                                .is_some_and(|adaptive| adaptive.converged(&estimate))
+                        {
+                            break;
+                        }
+                    }
+                    (estimate.mean, estimate.count)
+                },
+            )
             .collect_into_vec(&mut buf);
 
-        buf.iter().for_each(|x| println!("{}", x.write()));
+        buf.into_iter().unzip()
+    }
 
-        let duration: Duration = start.elapsed();
-        info!("Done. Time: {:?}.", duration);
+    fn write_sample_heatmap(&self, path: &Path, counts: &[u32]) -> std::io::Result<()> {
+        let mut ppm: String = format!("P3\n{} {}\n255\n", self.image_width, self.image_height);
+        for count in counts {
+            let level: u8 =
+                (255.999 * f64::from(*count) / f64::from(self.samples_per_pixel)).trunc() as u8;
+            ppm.push_str(&format!("{level} {level} {level}\n"));
+        }
+        std::fs::write(path, ppm)
     }
 
-    pub fn test_colorizer(
-        ray: &Ray,
-        world: Arc<HittableList>,
-        time: Interval,
-        depth: i32,
-    ) -> Color {
-        if depth <= 0 {
-            return Color::new(0., 0., 0.);
+    fn write_image(&self, path: &Path, image: &[Color]) -> std::io::Result<()> {
+        let mut ppm: String = format!("P3\n{} {}\n255\n", self.image_width, self.image_height);
+        for pixel in image {
+            ppm.push_str(&format!("{}\n", pixel.write()));
         }
+        std::fs::write(path, ppm)
+    }
 
-        world
-            .hit(ray, time)
-            .map(|hit_record| {
-                hit_record
-                    .material()
-                    .scatter(ray, &hit_record)
-                    .map(|scattering| {
-                        scattering.attenuation().component_mul(
-                            &(Self::test_colorizer(
-                                scattering.ray(),
-                                world.clone(),
-                                time,
-                                depth - 1,
-                            )),
-                        )
-                    })
-                    .unwrap_or(Color::new(0., 0., 0.))
-            })
-            .unwrap_or(Self::sky_box(ray))
+    /// Prints `image`, as returned by `render_image`, to stdout as a PPM.
+    pub fn print_image(&self, image: &[Color]) {
+        println!("P3");
+        println!("{} {}", self.image_width, self.image_height);
+        println!("255");
+
+        image.iter().for_each(|x| println!("{}", x.write()));
     }
 
-    pub fn sky_box(ray: &Ray) -> Color {
-        let a: f64 = 0.5 * (ray.direction().normalize().y() + 1.);
-        (1. - a) * Color::new(1., 1., 1.) + a * Color::new(0.5, 0.7, 1.)
+    pub fn render(&self, world: HittableList, integrator: &dyn Integrator, time: Interval) {
+        use log::{error, info};
+        use std::time::{Duration, Instant};
+
+        env_logger::init();
+
+        info!("Rendering started.");
+
+        let start = Instant::now();
+
+        let (buf, counts) = self.render_image_with_counts(&world, integrator, time);
+
+        self.print_image(&buf);
+
+        if let Some(path) = &self.sample_heatmap {
+            match self.write_sample_heatmap(path, &counts) {
+                Ok(()) => info!("Sample heatmap written to {}.", path.display()),
+                Err(error) => error!("Failed to write sample heatmap: {error}."),
+            }
+        }
+        for (path, pass) in &self.render_passes {
+            let image: Vec<Color> = self.render_pass(&world, pass.as_ref(), time, &counts);
+            match self.write_image(path, &image) {
+                Ok(()) => info!("Render pass written to {}.", path.display()),
+                Err(error) => error!("Failed to write render pass: {error}."),
+            }
+        }
+        let total: u64 = counts.iter().map(|&count| u64::from(count)).sum();
+        info!(
+            "Average samples per pixel: {:.1}.",
+            total as f64 / counts.len() as f64
+        );
+
+        let duration: Duration = start.elapsed();
+        info!("Done. Time: {:?}.", duration);
     }
 }
@@ -1,7 +1,11 @@
 use rand::Rng;
 use rand_distr::{Distribution, Uniform};
 
-use crate::vector::Vector;
+use crate::{
+    PI,
+    microfacet::TrowbridgeReitz,
+    vector::{Point, Vector},
+};
 
 pub struct UniformOffset2D {
     min: f64,
@@ -48,43 +52,213 @@ impl Distribution<Vector> for UniformVec3D {
 
 pub struct UniformUnitVec3D;
 
-impl UniformUnitVec3D {
-    pub fn random_unit_vector() -> Vector {
-        let uniform = UniformUnitVec3D;
-        uniform.sample(&mut rand::rng())
+impl Distribution<Vector> for UniformUnitVec3D {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vector {
+        let uniform = UniformVec3D::new(-1., 1.);
+        loop {
+            let vec: Vector = uniform.sample(rng);
+            let norm_squared = vec.norm_squared();
+            if 1e-160 < norm_squared && norm_squared <= 1. {
+                return vec.normalize();
+            }
+        }
     }
+}
+
+/// Maps the unit square onto the unit disk, keeping strata compact
+/// (Shirley and Chiu's concentric mapping).
+pub struct ConcentricDisk;
 
-    pub fn random_on_hemisphere(normal: &Vector) -> Vector {
-        let uniform = UniformUnitVec3D;
-        let unit_vec: Vector = uniform.sample(&mut rand::rng());
-        if normal.dot(&unit_vec) > 0. {
-            unit_vec
+impl ConcentricDisk {
+    pub fn warp(&self, u: (f64, f64)) -> (f64, f64) {
+        let (x, y) = (2. * u.0 - 1., 2. * u.1 - 1.);
+        if x == 0. && y == 0. {
+            return (0., 0.);
+        }
+        let (r, theta) = if x.abs() > y.abs() {
+            (x, PI / 4. * (y / x))
+        } else {
+            (y, PI / 2. - PI / 4. * (x / y))
+        };
+        (r * theta.cos(), r * theta.sin())
+    }
+
+    /// Density with respect to area.
+    pub fn pdf(&self, point: (f64, f64)) -> f64 {
+        if point.0.powi(2) + point.1.powi(2) <= 1. {
+            1. / PI
         } else {
-            -unit_vec
+            0.
         }
     }
+}
 
-    pub fn random_in_unit_disk() -> (f64, f64) {
-        let uniform = UniformOffset2D::new(-1., 1.);
-        let mut rng = rand::rng();
-        loop {
-            let (x, y) = uniform.sample(&mut rng);
-            if x.powi(2) + y.powi(2) < 1. {
-                return (x, y);
-            }
+impl Distribution<(f64, f64)> for ConcentricDisk {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> (f64, f64) {
+        self.warp(rng.random())
+    }
+}
+
+/// Directions about `+z` with density proportional to their cosine.
+pub struct CosineHemisphere;
+
+impl CosineHemisphere {
+    pub fn warp(&self, u: (f64, f64)) -> Vector {
+        let (x, y) = ConcentricDisk.warp(u);
+        Vector::new(x, y, (1. - x.powi(2) - y.powi(2)).max(0.).sqrt())
+    }
+
+    /// Density with respect to solid angle.
+    pub fn pdf(&self, w: &Vector) -> f64 {
+        w.z.max(0.) / PI
+    }
+}
+
+impl Distribution<Vector> for CosineHemisphere {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vector {
+        self.warp(rng.random())
+    }
+}
+
+/// Directions uniformly distributed over the unit sphere.
+pub struct UniformSphere;
+
+impl UniformSphere {
+    pub fn warp(&self, u: (f64, f64)) -> Vector {
+        let z: f64 = 1. - 2. * u.0;
+        let r: f64 = (1. - z.powi(2)).max(0.).sqrt();
+        let phi: f64 = 2. * PI * u.1;
+        Vector::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    /// Density with respect to solid angle.
+    pub fn pdf(&self, _w: &Vector) -> f64 {
+        1. / (4. * PI)
+    }
+}
+
+impl Distribution<Vector> for UniformSphere {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vector {
+        self.warp(rng.random())
+    }
+}
+
+/// Directions uniformly distributed within `cosine_max` of `+z`.
+pub struct UniformCone {
+    cosine_max: f64,
+}
+
+impl UniformCone {
+    pub fn new(cosine_max: f64) -> Self {
+        UniformCone {
+            cosine_max: cosine_max.clamp(-1., 1.),
+        }
+    }
+
+    pub fn warp(&self, u: (f64, f64)) -> Vector {
+        let cosine: f64 = 1. - u.0 * (1. - self.cosine_max);
+        let sine: f64 = (1. - cosine.powi(2)).max(0.).sqrt();
+        let phi: f64 = 2. * PI * u.1;
+        Vector::new(phi.cos() * sine, phi.sin() * sine, cosine)
+    }
+
+    /// Density with respect to solid angle.
+    pub fn pdf(&self, w: &Vector) -> f64 {
+        if w.normalize().z >= self.cosine_max {
+            1. / (2. * PI * (1. - self.cosine_max))
+        } else {
+            0.
         }
     }
 }
 
-impl Distribution<Vector> for UniformUnitVec3D {
+impl Distribution<Vector> for UniformCone {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vector {
-        let uniform = UniformVec3D::new(-1., 1.);
-        loop {
-            let vec: Vector = uniform.sample(rng);
-            let norm_squared = vec.norm_squared();
-            if 1e-160 < norm_squared && norm_squared <= 1. {
-                return vec.normalize();
-            }
+        self.warp(rng.random())
+    }
+}
+
+/// Points uniformly distributed over a triangle.
+pub struct UniformTriangle {
+    vertices: [Point; 3],
+}
+
+impl UniformTriangle {
+    pub fn new(a: Point, b: Point, c: Point) -> Self {
+        UniformTriangle {
+            vertices: [a, b, c],
         }
     }
+
+    pub fn area(&self) -> f64 {
+        let [a, b, c] = self.vertices;
+        (b - a).cross(&(c - a)).norm() / 2.
+    }
+
+    pub fn warp(&self, u: (f64, f64)) -> Point {
+        let [a, b, c] = self.vertices;
+        let root: f64 = u.0.sqrt();
+        let (b0, b1) = (1. - root, u.1 * root);
+        Point::from(b0 * a.coords + b1 * b.coords + (1. - b0 - b1) * c.coords)
+    }
+
+    /// Density with respect to area, for points on the triangle.
+    pub fn pdf(&self, _point: &Point) -> f64 {
+        1. / self.area()
+    }
+}
+
+impl Distribution<Point> for UniformTriangle {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Point {
+        self.warp(rng.random())
+    }
+}
+
+/// Microfacet normals of a GGX distribution visible from `wo` (Heitz 2018), in
+/// the local frame of the distribution.
+pub struct GgxVndf {
+    distribution: TrowbridgeReitz,
+    wo: Vector,
+}
+
+impl GgxVndf {
+    pub fn new(distribution: TrowbridgeReitz, wo: Vector) -> Self {
+        GgxVndf { distribution, wo }
+    }
+
+    pub fn warp(&self, u: (f64, f64)) -> Vector {
+        let (alpha_x, alpha_y) = (self.distribution.alpha_x(), self.distribution.alpha_y());
+        let flip: bool = self.wo.z < 0.;
+        let w: Vector = if flip { -self.wo } else { self.wo };
+
+        let wh: Vector = Vector::new(alpha_x * w.x, alpha_y * w.y, w.z).normalize();
+        let length_2: f64 = wh.x.powi(2) + wh.y.powi(2);
+        let t1: Vector = if length_2 > 0. {
+            Vector::new(-wh.y, wh.x, 0.) / length_2.sqrt()
+        } else {
+            Vector::new(1., 0., 0.)
+        };
+        let t2: Vector = wh.cross(&t1);
+
+        let r: f64 = u.0.sqrt();
+        let phi: f64 = 2. * PI * u.1;
+        let p1: f64 = r * phi.cos();
+        let s: f64 = 0.5 * (1. + wh.z);
+        let p2: f64 = (1. - s) * (1. - p1.powi(2)).sqrt() + s * r * phi.sin();
+        let nh: Vector = p1 * t1 + p2 * t2 + (1. - p1.powi(2) - p2.powi(2)).max(0.).sqrt() * wh;
+
+        let wm: Vector = Vector::new(alpha_x * nh.x, alpha_y * nh.y, nh.z.max(1e-6)).normalize();
+        if flip { -wm } else { wm }
+    }
+
+    /// Density of `wm` with respect to solid angle.
+    pub fn pdf(&self, wm: &Vector) -> f64 {
+        self.distribution.d_visible(&self.wo, wm)
+    }
+}
+
+impl Distribution<Vector> for GgxVndf {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vector {
+        self.warp(rng.random())
+    }
 }
@@ -1,17 +1,58 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
 use crate::{
+    INFINITY, PI,
+    distribution::{UniformCone, UniformSphere},
     interval::Interval,
     material::Material,
-    ray::Ray,
-    vector::{Point, Vector},
+    ray::{Ray, RayDifferentials},
+    sampler::Sampler,
+    texture::NormalPerturbation,
+    vector::{Onb, Point, Vector},
 };
-use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct HitRecord {
     point: Point,
     normal: Vector,
+    shading_normal: Vector,
     material: Arc<dyn Material>,
     t: f64,
     front_face: bool,
+    u: f64,
+    v: f64,
+    dpdu: Vector,
+    dpdv: Vector,
+    dndu: Vector,
+    dndv: Vector,
+    object_index: usize,
+    object_id: usize,
+}
+
+/// Screen-space derivatives of a hit, derived from the differentials of its ray.
+#[derive(Debug, Clone, Copy)]
+pub struct SurfaceDifferentials {
+    pub dpdx: Vector,
+    pub dpdy: Vector,
+    pub dudx: f64,
+    pub dvdx: f64,
+    pub dudy: f64,
+    pub dvdy: f64,
+}
+
+impl SurfaceDifferentials {
+    /// Conservative width of the footprint in uv space.
+    pub fn uv_width(&self) -> f64 {
+        2. * self
+            .dudx
+            .abs()
+            .max(self.dvdx.abs())
+            .max(self.dudy.abs())
+            .max(self.dvdy.abs())
+    }
 }
 
 impl HitRecord {
@@ -25,20 +66,231 @@ impl HitRecord {
         HitRecord {
             point,
             normal,
+            shading_normal: normal,
             material,
             t,
             front_face,
+            u: 0.,
+            v: 0.,
+            dpdu: Vector::zeros(),
+            dpdv: Vector::zeros(),
+            dndu: Vector::zeros(),
+            dndv: Vector::zeros(),
+            object_index: 0,
+            object_id: 0,
         }
     }
 
+    pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.u = u;
+        self.v = v;
+        self
+    }
+
+    /// Partial derivatives of the surface position, following the outward normal.
+    pub fn with_tangents(mut self, dpdu: Vector, dpdv: Vector) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    /// Partial derivatives of the outward normal.
+    pub fn with_normal_derivatives(mut self, dndu: Vector, dndv: Vector) -> Self {
+        self.dndu = dndu;
+        self.dndv = dndv;
+        self
+    }
+
+    /// Position of the hit object in the outermost list that contains it.
+    pub fn with_object_index(mut self, object_index: usize) -> Self {
+        self.object_index = object_index;
+        self
+    }
+
+    /// Identity of the primitive hit, distinct even between objects sharing a
+    /// material. Media inside objects are tracked by it.
+    pub fn with_object_id(mut self, object_id: usize) -> Self {
+        self.object_id = object_id;
+        self
+    }
+
     pub fn point(&self) -> Point {
         self.point
     }
 
+    /// Geometric normal, always facing against the incoming ray.
     pub fn normal(&self) -> Vector {
         self.normal
     }
 
+    /// Normal used for shading, on the same side as the geometric normal.
+    pub fn shading_normal(&self) -> Vector {
+        self.shading_normal
+    }
+
+    pub fn set_shading_normal(&mut self, shading_normal: Vector) {
+        self.shading_normal = self.face_forward(shading_normal);
+    }
+
+    pub fn outward_normal(&self) -> Vector {
+        if self.front_face {
+            self.normal
+        } else {
+            -self.normal
+        }
+    }
+
+    /// Flips `vector` onto the side of the geometric normal.
+    pub fn face_forward(&self, vector: Vector) -> Vector {
+        if vector.dot(&self.normal) < 0. {
+            -vector
+        } else {
+            vector
+        }
+    }
+
+    pub fn uv(&self) -> (f64, f64) {
+        (self.u, self.v)
+    }
+
+    pub fn object_index(&self) -> usize {
+        self.object_index
+    }
+
+    pub fn object_id(&self) -> usize {
+        self.object_id
+    }
+
+    pub fn dpdu(&self) -> Vector {
+        self.dpdu
+    }
+
+    pub fn dpdv(&self) -> Vector {
+        self.dpdv
+    }
+
+    pub fn differentials(&self, ray: &Ray) -> Option<SurfaceDifferentials> {
+        let differentials: &RayDifferentials = ray.differentials()?;
+        let normal: Vector = self.normal;
+        let d: f64 = normal.dot(&self.point.coords);
+
+        let plane_hit = |origin: Point, direction: Vector| -> Option<Point> {
+            let denominator: f64 = normal.dot(&direction);
+            if denominator.abs() < 1e-12 {
+                return None;
+            }
+            let t: f64 = (d - normal.dot(&origin.coords)) / denominator;
+            Some(origin + t * direction)
+        };
+
+        let dpdx: Vector =
+            plane_hit(differentials.rx_origin(), differentials.rx_direction())? - self.point;
+        let dpdy: Vector =
+            plane_hit(differentials.ry_origin(), differentials.ry_direction())? - self.point;
+
+        // Solve the overdetermined system on the two axes least aligned with the normal.
+        let (dim_0, dim_1) = if normal.x.abs() > normal.y.abs() && normal.x.abs() > normal.z.abs() {
+            (1, 2)
+        } else if normal.y.abs() > normal.z.abs() {
+            (0, 2)
+        } else {
+            (0, 1)
+        };
+        let a: [[f64; 2]; 2] = [
+            [self.dpdu[dim_0], self.dpdv[dim_0]],
+            [self.dpdu[dim_1], self.dpdv[dim_1]],
+        ];
+        let determinant: f64 = a[0][0] * a[1][1] - a[0][1] * a[1][0];
+
+        let solve = |b: &Vector| -> (f64, f64) {
+            if determinant.abs() < 1e-12 {
+                return (0., 0.);
+            }
+            let du: f64 = (a[1][1] * b[dim_0] - a[0][1] * b[dim_1]) / determinant;
+            let dv: f64 = (a[0][0] * b[dim_1] - a[1][0] * b[dim_0]) / determinant;
+            (du, dv)
+        };
+        let (dudx, dvdx) = solve(&dpdx);
+        let (dudy, dvdy) = solve(&dpdy);
+
+        Some(SurfaceDifferentials {
+            dpdx,
+            dpdy,
+            dudx,
+            dvdx,
+            dudy,
+            dvdy,
+        })
+    }
+
+    /// Differentials of a ray mirrored about the shading normal into `out_direction`.
+    pub fn reflected_differentials(
+        &self,
+        ray_in: &Ray,
+        out_direction: &Vector,
+    ) -> Option<RayDifferentials> {
+        let surface: SurfaceDifferentials = self.differentials(ray_in)?;
+        let differentials: &RayDifferentials = ray_in.differentials()?;
+        let normal: Vector = self.shading_normal;
+        let (dndx, dndy) = self.normal_differentials(&surface);
+
+        let wo: Vector = -ray_in.direction().normalize();
+        let wi: Vector = out_direction.normalize();
+
+        let reflect = |aux_direction: Vector, dndx: Vector| -> Vector {
+            let dwodx: Vector = -aux_direction.normalize() - wo;
+            let ddndx: f64 = dwodx.dot(&normal) + wo.dot(&dndx);
+            wi - dwodx + 2. * (wo.dot(&normal) * dndx + ddndx * normal)
+        };
+
+        Some(RayDifferentials::new(
+            self.point + surface.dpdx,
+            reflect(differentials.rx_direction(), dndx),
+            self.point + surface.dpdy,
+            reflect(differentials.ry_direction(), dndy),
+        ))
+    }
+
+    /// Differentials of a ray refracted into `out_direction`, where `ri` is the
+    /// ratio of the incident over the transmitted refractive index.
+    pub fn refracted_differentials(
+        &self,
+        ray_in: &Ray,
+        out_direction: &Vector,
+        ri: f64,
+    ) -> Option<RayDifferentials> {
+        let surface: SurfaceDifferentials = self.differentials(ray_in)?;
+        let differentials: &RayDifferentials = ray_in.differentials()?;
+        let normal: Vector = self.shading_normal;
+        let (dndx, dndy) = self.normal_differentials(&surface);
+
+        let wo: Vector = -ray_in.direction().normalize();
+        let wi: Vector = out_direction.normalize();
+        let cosine_out: f64 = wi.dot(&normal).abs().max(1e-8);
+        let mu: f64 = ri * wo.dot(&normal) - cosine_out;
+
+        let refract = |aux_direction: Vector, dndx: Vector| -> Vector {
+            let dwodx: Vector = -aux_direction.normalize() - wo;
+            let ddndx: f64 = dwodx.dot(&normal) + wo.dot(&dndx);
+            let dmudx: f64 = (ri - ri.powi(2) * wo.dot(&normal) / cosine_out) * ddndx;
+            wi - ri * dwodx + mu * dndx + dmudx * normal
+        };
+
+        Some(RayDifferentials::new(
+            self.point + surface.dpdx,
+            refract(differentials.rx_direction(), dndx),
+            self.point + surface.dpdy,
+            refract(differentials.ry_direction(), dndy),
+        ))
+    }
+
+    fn normal_differentials(&self, surface: &SurfaceDifferentials) -> (Vector, Vector) {
+        let sign: f64 = if self.front_face { 1. } else { -1. };
+        let dndx: Vector = sign * (self.dndu * surface.dudx + self.dndv * surface.dvdx);
+        let dndy: Vector = sign * (self.dndu * surface.dudy + self.dndv * surface.dvdy);
+        (dndx, dndy)
+    }
+
     pub fn material(&self) -> Arc<dyn Material> {
         self.material.clone()
     }
@@ -54,6 +306,27 @@ impl HitRecord {
 
 pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, time: Interval) -> Option<HitRecord>;
+
+    /// Solid-angle density of `random` choosing `direction` from `origin`.
+    fn pdf_value(&self, _origin: &Point, _direction: &Vector) -> f64 {
+        0.
+    }
+
+    /// Direction from `origin` towards the object, used to sample it as a light.
+    fn random(&self, _origin: &Point, _sampler: &mut dyn Sampler) -> Vector {
+        Vector::x()
+    }
+
+    /// Point sampled on the surface, as a hit seen from outside, with its
+    /// density per unit area. Used to start light paths.
+    fn sample_surface(&self, _sampler: &mut dyn Sampler) -> Option<(HitRecord, f64)> {
+        None
+    }
+
+    /// Density per unit area of `sample_surface` choosing `point`.
+    fn surface_pdf(&self, _point: &Point) -> f64 {
+        0.
+    }
 }
 
 pub struct HittableList(Vec<Arc<dyn Hittable>>);
@@ -83,20 +356,63 @@ impl Hittable for HittableList {
         let mut closest = time.max();
         let mut result = None;
 
-        for obj in self.iter() {
+        for (index, obj) in self.iter().enumerate() {
             if let Some(rec) = obj.hit(ray, Interval::new(time.min(), closest)) {
                 closest = rec.t();
-                result = Some(rec);
+                result = Some(rec.with_object_index(index));
             }
         }
         result
     }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vector) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+        self.iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum::<f64>()
+            / self.len() as f64
+    }
+
+    fn random(&self, origin: &Point, sampler: &mut dyn Sampler) -> Vector {
+        if self.is_empty() {
+            return Vector::x();
+        }
+        let index: usize = ((sampler.get_1d() * self.len() as f64) as usize).min(self.len() - 1);
+        self[index].random(origin, sampler)
+    }
+
+    fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<(HitRecord, f64)> {
+        if self.is_empty() {
+            return None;
+        }
+        let index: usize = ((sampler.get_1d() * self.len() as f64) as usize).min(self.len() - 1);
+        let (hit_record, _) = self[index].sample_surface(sampler)?;
+        let pdf: f64 = self.surface_pdf(&hit_record.point());
+        Some((hit_record, pdf))
+    }
+
+    fn surface_pdf(&self, point: &Point) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+        self.iter()
+            .map(|object| object.surface_pdf(point))
+            .sum::<f64>()
+            / self.len() as f64
+    }
 }
 
+/// Source of `HitRecord::object_id`, starting at 1 so that hit records built
+/// without an object keep 0.
+static NEXT_OBJECT_ID: AtomicUsize = AtomicUsize::new(1);
+
 pub struct Sphere {
     center: Point,
     radius: f64,
     material: Arc<dyn Material>,
+    id: usize,
 }
 
 impl Sphere {
@@ -105,6 +421,7 @@ impl Sphere {
             center,
             radius,
             material,
+            id: NEXT_OBJECT_ID.fetch_add(1, Ordering::Relaxed),
         }
     }
 
@@ -115,6 +432,39 @@ impl Sphere {
     pub fn radius(&self) -> f64 {
         self.radius
     }
+
+    fn hit_record(&self, ray: &Ray, t: f64) -> HitRecord {
+        let point: Point = ray.at(t);
+        let outward_normal: Vector = (point - self.center) / self.radius;
+        let front_face: bool = ray.direction().dot(&outward_normal) < 0.;
+
+        let normal: Vector = if front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+
+        // Spherical coordinates measured from -y, with u increasing eastward from -x.
+        let unit: Vector = outward_normal;
+        let theta: f64 = (-unit.y).clamp(-1., 1.).acos();
+        let phi: f64 = (-unit.z).atan2(unit.x) + PI;
+        let sine_theta: f64 = theta.sin().max(1e-8);
+
+        let dpdu: Vector = 2. * PI * self.radius * Vector::new(unit.z, 0., -unit.x);
+        let dpdv: Vector = PI
+            * self.radius
+            * Vector::new(
+                -unit.x * unit.y / sine_theta,
+                sine_theta,
+                -unit.y * unit.z / sine_theta,
+            );
+
+        HitRecord::new(point, normal, self.material.clone(), t, front_face)
+            .with_uv(phi / (2. * PI), theta / PI)
+            .with_tangents(dpdu, dpdv)
+            .with_normal_derivatives(dpdu / self.radius, dpdv / self.radius)
+            .with_object_id(self.id)
+    }
 }
 
 impl Hittable for Sphere {
@@ -129,30 +479,97 @@ impl Hittable for Sphere {
             return None;
         }
 
-        let mut t: f64 = (h - disciminant.sqrt()) / a;
-        if !time.surrounds(t) {
-            t = (h + disciminant.sqrt()) / a;
-            if !time.surrounds(t) {
-                return None;
-            }
+        // Cut-out hits are skipped so the far side of the sphere can still be hit.
+        [(h - disciminant.sqrt()) / a, (h + disciminant.sqrt()) / a]
+            .into_iter()
+            .filter(|t| time.surrounds(*t))
+            .map(|t| self.hit_record(ray, t))
+            .find(|hit_record| self.material.is_opaque(ray, hit_record))
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vector) -> f64 {
+        let ray: Ray = Ray::new(*origin, *direction);
+        if self.hit(&ray, Interval::new(0.001, INFINITY)).is_none() {
+            return 0.;
         }
+        let distance_2: f64 = (self.center - origin).norm_squared();
+        if distance_2 <= self.radius.powi(2) {
+            return UniformSphere.pdf(direction);
+        }
+        UniformCone::new((1. - self.radius.powi(2) / distance_2).sqrt()).pdf(&Vector::z())
+    }
 
-        let point: Point = ray.at(t);
-        let outward_normal: Vector = (point - self.center) / self.radius;
-        let front_face: bool = ray.direction().dot(&outward_normal) < 0.;
+    /// Samples the cone of directions subtended by the sphere, or the whole
+    /// sphere of directions from inside it.
+    fn random(&self, origin: &Point, sampler: &mut dyn Sampler) -> Vector {
+        let to_center: Vector = self.center - origin;
+        let distance_2: f64 = to_center.norm_squared();
+        if distance_2 <= self.radius.powi(2) {
+            return UniformSphere.warp(sampler.get_2d());
+        }
 
-        let normal: Vector = if front_face {
-            outward_normal
-        } else {
-            -outward_normal
-        };
+        let cone = UniformCone::new((1. - self.radius.powi(2) / distance_2).sqrt());
+        Onb::new(&to_center.normalize()).to_world(&cone.warp(sampler.get_2d()))
+    }
 
-        Some(HitRecord::new(
-            point,
-            normal,
-            self.material.clone(),
-            t,
-            front_face,
-        ))
+    fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<(HitRecord, f64)> {
+        let normal: Vector = UniformSphere.warp(sampler.get_2d());
+        let ray: Ray = Ray::new(self.center + (self.radius + 1.) * normal, -normal);
+        Some((self.hit_record(&ray, 1.), self.surface_pdf(&ray.at(1.))))
+    }
+
+    fn surface_pdf(&self, point: &Point) -> f64 {
+        let distance: f64 = (point - self.center).norm();
+        if (distance - self.radius).abs() > 1e-6 * self.radius.max(1.) {
+            return 0.;
+        }
+        1. / (4. * PI * self.radius.powi(2))
+    }
+}
+
+/// Wraps another object and perturbs the shading normal of its hits.
+pub struct Perturbed {
+    object: Arc<dyn Hittable>,
+    perturbation: Arc<dyn NormalPerturbation>,
+}
+
+impl Perturbed {
+    pub fn new(object: Arc<dyn Hittable>, perturbation: Arc<dyn NormalPerturbation>) -> Self {
+        Perturbed {
+            object,
+            perturbation,
+        }
+    }
+}
+
+impl Hittable for Perturbed {
+    fn hit(&self, ray: &Ray, time: Interval) -> Option<HitRecord> {
+        self.object.hit(ray, time).map(|mut hit_record| {
+            let shading_normal: Vector = self.perturbation.perturb(&hit_record);
+            hit_record.set_shading_normal(shading_normal);
+            hit_record
+        })
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vector) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point, sampler: &mut dyn Sampler) -> Vector {
+        self.object.random(origin, sampler)
+    }
+
+    fn sample_surface(&self, sampler: &mut dyn Sampler) -> Option<(HitRecord, f64)> {
+        self.object
+            .sample_surface(sampler)
+            .map(|(mut hit_record, pdf)| {
+                let shading_normal: Vector = self.perturbation.perturb(&hit_record);
+                hit_record.set_shading_normal(shading_normal);
+                (hit_record, pdf)
+            })
+    }
+
+    fn surface_pdf(&self, point: &Point) -> f64 {
+        self.object.surface_pdf(point)
     }
 }
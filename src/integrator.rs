@@ -0,0 +1,451 @@
+use std::sync::Arc;
+
+use crate::{
+    color::Color,
+    distribution::CosineHemisphere,
+    hittable::{HitRecord, Hittable, HittableList},
+    interval::Interval,
+    material::Scattering,
+    ray::Ray,
+    sampler::{Sampler, hash},
+    spectrum,
+    vector::{Onb, R3, Vector},
+};
+
+/// Estimates the radiance arriving along a camera ray.
+pub trait Integrator: Sync + Send {
+    /// Spectral rays get the radiance at their wavelength in every channel.
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        time: Interval,
+        sampler: &mut dyn Sampler,
+    ) -> Color;
+
+    /// Called before the `radiance` calls of every render, to precompute what
+    /// depends on the scene. `spectral` tells whether rays carry wavelengths,
+    /// and random choices should derive from the camera's `seed`.
+    fn prepare(&self, _world: &HittableList, _time: Interval, _spectral: bool, _seed: u64) {}
+}
+
+/// How scattered directions are chosen at surfaces that can be evaluated.
+#[derive(Clone)]
+pub enum LightStrategy {
+    /// Follow the direction sampled by the material.
+    Bsdf,
+    /// Pick the material direction or a direction towards one of the lights
+    /// with equal probability, weighting by the density of the mixture. Light
+    /// sampling is limited to reflections.
+    Mixture(Arc<HittableList>),
+}
+
+#[derive(Clone)]
+pub struct IntegratorConfig {
+    max_depth: i32,
+    light_strategy: LightStrategy,
+    clamp: Option<f64>,
+}
+
+impl IntegratorConfig {
+    pub fn new(max_depth: i32) -> Self {
+        IntegratorConfig {
+            max_depth,
+            light_strategy: LightStrategy::Bsdf,
+            clamp: None,
+        }
+    }
+
+    pub fn with_light_strategy(mut self, light_strategy: LightStrategy) -> Self {
+        self.light_strategy = light_strategy;
+        self
+    }
+
+    /// Samples `lights` alongside the materials.
+    pub fn with_lights(self, lights: Arc<HittableList>) -> Self {
+        self.with_light_strategy(LightStrategy::Mixture(lights))
+    }
+
+    /// Scales down samples brighter than `max` in any channel, trading a little
+    /// energy for fewer fireflies.
+    pub fn with_clamp(mut self, max: f64) -> Self {
+        self.clamp = Some(max);
+        self
+    }
+
+    pub fn max_depth(&self) -> i32 {
+        self.max_depth
+    }
+
+    pub fn light_strategy(&self) -> &LightStrategy {
+        &self.light_strategy
+    }
+
+    /// The lights of the scene, when they were given.
+    pub fn lights(&self) -> Option<&Arc<HittableList>> {
+        match &self.light_strategy {
+            LightStrategy::Mixture(lights) => Some(lights),
+            LightStrategy::Bsdf => None,
+        }
+    }
+
+    pub fn clamped(&self, radiance: Color) -> Color {
+        match self.clamp {
+            Some(max) if radiance.max() > max => radiance * (max / radiance.max()),
+            _ => radiance,
+        }
+    }
+
+    /// The ray leaving a hit after `scattering`, and the factor by which it
+    /// weights the light it brings back, before spectral projection.
+    pub fn next_ray(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        scattering: Scattering,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
+        let lights: &HittableList = match &self.light_strategy {
+            LightStrategy::Mixture(lights) if scattering.pdf().is_some() && !lights.is_empty() => {
+                lights
+            }
+            _ => return Some((scattering.ray().clone(), scattering.attenuation())),
+        };
+
+        // Only reflections are light sampled, since a light-sampled ray cannot
+        // know which media it would enter by crossing the surface.
+        let reflected = |direction: &Vector| direction.dot(&hit_record.normal()) > 0.;
+
+        let point = hit_record.point();
+        let material = hit_record.material();
+        let ray: Ray = if sampler.get_1d() < 0.5 {
+            let direction: Vector = lights.random(&point, sampler);
+            if !reflected(&direction) {
+                return None;
+            }
+            ray_in.scattered(point, direction)
+        } else {
+            scattering.ray().clone()
+        };
+        let direction: Vector = ray.direction();
+
+        let light_pdf: f64 = if reflected(&direction) {
+            lights.pdf_value(&point, &direction)
+        } else {
+            0.
+        };
+        let pdf: f64 = 0.5 * light_pdf + 0.5 * material.pdf(ray_in, hit_record, &direction);
+        if pdf <= 0. {
+            return None;
+        }
+        let weight: Color = material.eval(ray_in, hit_record, &direction) / pdf;
+
+        Some((ray, weight))
+    }
+}
+
+/// Light arriving from outside the scene.
+pub fn sky_box(ray: &Ray) -> Color {
+    let a: f64 = 0.5 * (ray.direction().normalize().y() + 1.);
+    (1. - a) * Color::new(1., 1., 1.) + a * Color::new(0.5, 0.7, 1.)
+}
+
+/// Recursive path tracer, following one scattered ray per bounce until the
+/// maximum depth.
+pub struct RecursiveIntegrator {
+    config: IntegratorConfig,
+}
+
+impl RecursiveIntegrator {
+    pub fn new(config: IntegratorConfig) -> Self {
+        RecursiveIntegrator { config }
+    }
+
+    fn trace(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        time: Interval,
+        depth: i32,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        if depth <= 0 {
+            return Color::new(0., 0., 0.);
+        }
+
+        world
+            .hit(ray, time)
+            .map(|hit_record| {
+                // Light is absorbed by the medium the ray travelled through.
+                let transmittance: Color = spectrum::project(
+                    ray.media()
+                        .transmittance(hit_record.t() * ray.direction().norm()),
+                    ray.wavelength(),
+                );
+
+                let material = hit_record.material();
+                let emitted: Color =
+                    spectrum::project(material.emitted(ray, &hit_record), ray.wavelength());
+
+                let scattered: Color = material
+                    .scatter(ray, &hit_record, sampler)
+                    .and_then(|scattering| {
+                        self.config.next_ray(ray, &hit_record, scattering, sampler)
+                    })
+                    .map(|(next, weight)| {
+                        spectrum::project(weight, ray.wavelength()).component_mul(&self.trace(
+                            &next,
+                            world,
+                            time,
+                            depth - 1,
+                            sampler,
+                        ))
+                    })
+                    .unwrap_or(Color::new(0., 0., 0.));
+
+                transmittance.component_mul(&(emitted + scattered))
+            })
+            .unwrap_or(spectrum::project(sky_box(ray), ray.wavelength()))
+    }
+}
+
+impl Integrator for RecursiveIntegrator {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        time: Interval,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        self.config
+            .clamped(self.trace(ray, world, time, self.config.max_depth(), sampler))
+    }
+}
+
+/// Iterative path tracer tracking the throughput of the path. Past a few
+/// bounces, paths are terminated at random with a probability that grows as
+/// their throughput drops, and survivors are reweighted to stay unbiased.
+pub struct PathIntegrator {
+    config: IntegratorConfig,
+    roulette_depth: i32,
+}
+
+impl PathIntegrator {
+    pub fn new(config: IntegratorConfig) -> Self {
+        PathIntegrator {
+            config,
+            roulette_depth: 3,
+        }
+    }
+
+    /// Number of bounces always traced before Russian roulette starts.
+    pub fn with_roulette_depth(mut self, roulette_depth: i32) -> Self {
+        self.roulette_depth = roulette_depth.max(0);
+        self
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        time: Interval,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let wavelength: Option<f64> = ray.wavelength();
+        let mut radiance: Color = Color::zeros();
+        let mut throughput: Color = Color::repeat(1.);
+        let mut ray: Ray = ray.clone();
+
+        for depth in 0..self.config.max_depth() {
+            let Some(hit_record) = world.hit(&ray, time) else {
+                radiance += throughput.component_mul(&spectrum::project(sky_box(&ray), wavelength));
+                break;
+            };
+
+            let transmittance: Color = ray
+                .media()
+                .transmittance(hit_record.t() * ray.direction().norm());
+            throughput.component_mul_assign(&spectrum::project(transmittance, wavelength));
+
+            let material = hit_record.material();
+            let emitted: Color = material.emitted(&ray, &hit_record);
+            radiance += throughput.component_mul(&spectrum::project(emitted, wavelength));
+
+            let Some((next, weight)) =
+                material
+                    .scatter(&ray, &hit_record, sampler)
+                    .and_then(|scattering| {
+                        self.config.next_ray(&ray, &hit_record, scattering, sampler)
+                    })
+            else {
+                break;
+            };
+            throughput.component_mul_assign(&spectrum::project(weight, wavelength));
+
+            if depth >= self.roulette_depth {
+                let survival: f64 = throughput.max().min(0.95);
+                if survival <= 0. || sampler.get_1d() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+
+            ray = next;
+        }
+
+        self.config.clamped(radiance)
+    }
+}
+
+/// Ambient occlusion at the first hit: the fraction of cosine-weighted
+/// directions that leave the surface without hitting anything within `radius`.
+/// Rays that miss the scene are black.
+pub struct AmbientOcclusionIntegrator {
+    radius: f64,
+    samples: u32,
+}
+
+impl AmbientOcclusionIntegrator {
+    pub fn new(radius: f64, samples: u32) -> Self {
+        AmbientOcclusionIntegrator {
+            radius,
+            samples: samples.max(1),
+        }
+    }
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        time: Interval,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let Some(hit_record) = world.hit(ray, time) else {
+            return Color::zeros();
+        };
+
+        // Bumped shading normals may lean into the surface; the geometry does not.
+        let onb = Onb::new(&hit_record.normal());
+        let unoccluded: u32 = (0..self.samples)
+            .filter(|_| {
+                let direction: Vector = onb.to_world(&CosineHemisphere.warp(sampler.get_2d()));
+                let occlusion: Ray = ray.scattered(hit_record.point(), direction);
+                world
+                    .hit(&occlusion, Interval::new(time.min(), self.radius))
+                    .is_none()
+            })
+            .count() as u32;
+
+        Color::repeat(f64::from(unoccluded) / f64::from(self.samples))
+    }
+}
+
+/// What `DebugIntegrator` shows at the first hit.
+#[derive(Clone, Copy, Debug)]
+pub enum DebugView {
+    /// Geometric normal, facing the camera, mapped from `[-1, 1]` to `[0, 1]`.
+    GeometricNormal,
+    /// Geometric normal pointing out of the object, whichever side was hit.
+    OutwardNormal,
+    ShadingNormal,
+    /// Green where the ray hits the outside of a surface, red on the inside.
+    FrontFace,
+    /// Distance to the hit, white at the camera and black from `max_distance` on.
+    Depth(f64),
+    /// `u` in red and `v` in green.
+    Uv,
+    /// A colour per object of the world.
+    ObjectId,
+    /// A colour per material, stable within one run.
+    MaterialId,
+    /// Scattering events before the path ends, white at `max_depth`.
+    Bounces(i32),
+}
+
+/// Shades by properties of the hit instead of light, to inspect geometry and
+/// materials. Rays that miss the scene are black.
+pub struct DebugIntegrator {
+    view: DebugView,
+}
+
+impl DebugIntegrator {
+    pub fn new(view: DebugView) -> Self {
+        DebugIntegrator { view }
+    }
+
+    fn bounces(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        time: Interval,
+        max_depth: i32,
+        sampler: &mut dyn Sampler,
+    ) -> i32 {
+        let mut ray: Ray = ray.clone();
+        for depth in 0..max_depth {
+            let Some(scattering) = world
+                .hit(&ray, time)
+                .and_then(|hit_record| hit_record.material().scatter(&ray, &hit_record, sampler))
+            else {
+                return depth;
+            };
+            ray = scattering.ray().clone();
+        }
+        max_depth
+    }
+}
+
+/// A bright colour picked by `id`.
+fn id_color(id: u64) -> Color {
+    let bits: u64 = hash(&[id]);
+    Color::new(
+        (bits & 0xff) as f64,
+        (bits >> 8 & 0xff) as f64,
+        (bits >> 16 & 0xff) as f64,
+    ) / 255.
+        * 0.8
+        + Color::repeat(0.2)
+}
+
+impl Integrator for DebugIntegrator {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        time: Interval,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let Some(hit_record) = world.hit(ray, time) else {
+            return Color::zeros();
+        };
+        match self.view {
+            DebugView::GeometricNormal => (hit_record.normal() + Color::repeat(1.)) / 2.,
+            DebugView::OutwardNormal => (hit_record.outward_normal() + Color::repeat(1.)) / 2.,
+            DebugView::ShadingNormal => (hit_record.shading_normal() + Color::repeat(1.)) / 2.,
+            DebugView::FrontFace => match hit_record.front_face() {
+                true => Color::new(0., 1., 0.),
+                false => Color::new(1., 0., 0.),
+            },
+            DebugView::Depth(max_distance) => {
+                let distance: f64 = hit_record.t() * ray.direction().norm();
+                Color::repeat((1. - distance / max_distance).clamp(0., 1.))
+            }
+            DebugView::Uv => {
+                let (u, v) = hit_record.uv();
+                Color::new(u, v, 0.)
+            }
+            DebugView::ObjectId => id_color(hit_record.object_index() as u64),
+            DebugView::MaterialId => {
+                id_color(Arc::as_ptr(&hit_record.material()) as *const () as u64)
+            }
+            DebugView::Bounces(max_depth) => {
+                let bounces: i32 = self.bounces(ray, world, time, max_depth, sampler);
+                Color::repeat(f64::from(bounces) / f64::from(max_depth.max(1)))
+            }
+        }
+    }
+}
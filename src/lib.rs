@@ -1,10 +1,19 @@
+pub mod bdpt;
 pub mod camera;
 pub mod color;
 pub mod distribution;
 pub mod hittable;
+pub mod integrator;
 pub mod interval;
 pub mod material;
+pub mod medium;
+pub mod microfacet;
+pub mod mlt;
+pub mod photon;
 pub mod ray;
+pub mod sampler;
+pub mod spectrum;
+pub mod texture;
 pub mod vector;
 
 pub const INFINITY: f64 = f64::INFINITY;
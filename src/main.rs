@@ -1,19 +1,33 @@
 use ray_tracer::{
     INFINITY,
+    bdpt::BdptIntegrator,
     camera::Camera,
     color::Color,
     hittable::{HittableList, Sphere},
+    integrator::{
+        AmbientOcclusionIntegrator, DebugIntegrator, DebugView, Integrator, IntegratorConfig,
+        PathIntegrator, RecursiveIntegrator,
+    },
     interval::Interval,
-    material::{Dielectric, Lambertian, Metal},
+    material::{Dielectric, DiffuseLight, Lambertian, Metal},
+    mlt::MltRenderer,
+    photon::PhotonIntegrator,
     vector::{Point, Vector},
 };
 use std::sync::Arc;
 
+const INTEGRATORS: &str = "recursive, path, bdpt, photon, mlt, ao, normals, outward-normals, \
+                           shading-normals, front-face, depth, uv, object-id, material-id, \
+                           bounces";
+
+/// Renders the test scene to stdout with the integrator named by the first
+/// argument, the recursive path tracer by default.
 fn main() {
-    test_scene();
+    let integrator: String = std::env::args().nth(1).unwrap_or(String::from("recursive"));
+    test_scene(&integrator);
 }
 
-fn test_scene() {
+fn test_scene(integrator: &str) {
     let image_width: i32 = 1200;
     let aspect_ratio: f64 = 16. / 9.;
     let vfov: f64 = 80.;
@@ -35,7 +49,6 @@ fn test_scene() {
         defocus_angle,
         focus_dist,
         samples_per_pixel,
-        max_depth,
     );
 
     let mut world = HittableList::new(Vec::new());
@@ -50,11 +63,12 @@ fn test_scene() {
 
     let material_1: Arc<Dielectric> = Arc::new(Dielectric::new(1.5));
 
-    world.push(Arc::new(Sphere::new(
+    let glass_sphere: Arc<Sphere> = Arc::new(Sphere::new(
         Point::new(0.3, 1., 0.3),
         1.,
         material_1.clone(),
-    )));
+    ));
+    world.push(glass_sphere.clone());
 
     let material_2: Arc<Lambertian> = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
 
@@ -72,9 +86,60 @@ fn test_scene() {
         material_3.clone(),
     )));
 
-    camera.render(
-        world,
-        Camera::test_colorizer,
-        Interval::new(0.001, INFINITY),
-    );
+    let mut config: IntegratorConfig = IntegratorConfig::new(max_depth);
+
+    // Light subpaths need an emitter to start from, so bidirectional renders
+    // gain an area light.
+    if integrator == "bdpt" {
+        let material_light: Arc<DiffuseLight> =
+            Arc::new(DiffuseLight::new(Color::new(15., 14., 12.)));
+
+        let light: Arc<Sphere> = Arc::new(Sphere::new(
+            Point::new(-1.5, 5., 2.),
+            0.5,
+            material_light.clone(),
+        ));
+        world.push(light.clone());
+
+        config = config.with_lights(Arc::new(HittableList::new(vec![light])));
+    }
+    let time: Interval = Interval::new(0.001, INFINITY);
+
+    if integrator == "mlt" {
+        env_logger::init();
+        let image: Vec<Color> = MltRenderer::new(samples_per_pixel as u32).render(
+            &camera,
+            &world,
+            &PathIntegrator::new(config),
+            time,
+        );
+        camera.print_image(&image);
+        return;
+    }
+
+    let integrator: Box<dyn Integrator> = match integrator {
+        "recursive" => Box::new(RecursiveIntegrator::new(config)),
+        "path" => Box::new(PathIntegrator::new(config)),
+        "bdpt" => Box::new(BdptIntegrator::new(config)),
+        "photon" => Box::new(
+            PhotonIntegrator::new(config, 1_000_000)
+                .with_caustic_casters(Arc::new(HittableList::new(vec![glass_sphere]))),
+        ),
+        "ao" => Box::new(AmbientOcclusionIntegrator::new(1., 16)),
+        "normals" => Box::new(DebugIntegrator::new(DebugView::GeometricNormal)),
+        "outward-normals" => Box::new(DebugIntegrator::new(DebugView::OutwardNormal)),
+        "shading-normals" => Box::new(DebugIntegrator::new(DebugView::ShadingNormal)),
+        "front-face" => Box::new(DebugIntegrator::new(DebugView::FrontFace)),
+        "depth" => Box::new(DebugIntegrator::new(DebugView::Depth(10.))),
+        "uv" => Box::new(DebugIntegrator::new(DebugView::Uv)),
+        "object-id" => Box::new(DebugIntegrator::new(DebugView::ObjectId)),
+        "material-id" => Box::new(DebugIntegrator::new(DebugView::MaterialId)),
+        "bounces" => Box::new(DebugIntegrator::new(DebugView::Bounces(max_depth))),
+        _ => {
+            eprintln!("Unknown integrator `{integrator}`, expected one of: {INTEGRATORS}.");
+            std::process::exit(2);
+        }
+    };
+
+    camera.render(world, integrator.as_ref(), time);
 }
@@ -1,21 +1,57 @@
-use rand_distr::Distribution;
+use nalgebra::Complex;
+use std::sync::Arc;
 
 use crate::{
+    PI,
     color::Color,
-    distribution::UniformUnitVec3D,
+    distribution::{CosineHemisphere, UniformSphere},
     hittable::HitRecord,
+    medium::{FreeFlight, HomogeneousMedium, MediumBoundary, MediumEntry},
+    microfacet::{self, TrowbridgeReitz},
     ray::Ray,
-    vector::{R3, Vector},
+    sampler::Sampler,
+    spectrum::RefractiveIndex,
+    texture::{SolidColor, Texture},
+    vector::{Onb, R3, Vector},
 };
 
 pub struct Scattering {
     ray: Ray,
     attenuation: Color,
+    pdf: Option<f64>,
+    specular: bool,
 }
 
 impl Scattering {
     pub fn new(ray: Ray, attenuation: Color) -> Self {
-        Scattering { ray, attenuation }
+        Scattering {
+            ray,
+            attenuation,
+            pdf: None,
+            specular: false,
+        }
+    }
+
+    /// Density of the sampled direction, for lobes that `Material::eval` and
+    /// `Material::pdf` also describe. Integrators may then replace the direction
+    /// with one sampled elsewhere, e.g. towards a light.
+    pub fn with_pdf(mut self, pdf: f64) -> Self {
+        self.pdf = Some(pdf);
+        self
+    }
+
+    /// Forgets the density, for wrapping materials whose own `eval` does not
+    /// describe the lobe that was sampled.
+    pub fn without_pdf(mut self) -> Self {
+        self.pdf = None;
+        self
+    }
+
+    /// Marks a perfectly sharp reflection or refraction, whose direction was
+    /// the only one the lobe could scatter into. Wrapping materials keep it.
+    pub fn with_specular(mut self, specular: bool) -> Self {
+        self.specular = specular;
+        self
     }
 
     pub fn ray(&self) -> &Ray {
@@ -25,32 +61,186 @@ impl Scattering {
     pub fn attenuation(&self) -> Color {
         self.attenuation
     }
+
+    /// `None` for specular and other lobes that can only be sampled.
+    pub fn pdf(&self) -> Option<f64> {
+        self.pdf
+    }
+
+    pub fn is_specular(&self) -> bool {
+        self.specular
+    }
+
+    /// Multiplies the attenuation, e.g. to account for choosing between layers.
+    pub fn scaled(mut self, weight: Color) -> Self {
+        self.attenuation = self.attenuation.component_mul(&weight);
+        self
+    }
 }
 
 pub trait Material: Sync + Send {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scattering>;
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering>;
+
+    /// Whether the surface exists at this hit. Objects skip hits on cut-out
+    /// parts while intersecting, so they are never shaded.
+    fn is_opaque(&self, _ray: &Ray, _hit_record: &HitRecord) -> bool {
+        true
+    }
+
+    fn emitted(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color {
+        Color::zeros()
+    }
+
+    /// `f * |cos|` for light leaving along `direction`, over the lobes whose
+    /// scatterings carry a density.
+    fn eval(&self, _ray_in: &Ray, _hit_record: &HitRecord, _direction: &Vector) -> Color {
+        Color::zeros()
+    }
+
+    /// Solid-angle density of `scatter` choosing `direction`.
+    fn pdf(&self, _ray_in: &Ray, _hit_record: &HitRecord, _direction: &Vector) -> f64 {
+        0.
+    }
+}
+
+/// Cosine of `direction` against the shading normal, or zero when it points
+/// into the surface on either normal.
+fn reflected_cosine(hit_record: &HitRecord, direction: &Vector) -> f64 {
+    let direction: Vector = direction.normalize();
+    if direction.dot(&hit_record.normal()) <= 0. {
+        return 0.;
+    }
+    direction.dot(&hit_record.shading_normal()).max(0.)
 }
 
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
+        Lambertian::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
         Lambertian { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Option<Scattering> {
-        let mut out_direction: Vector =
-            UniformUnitVec3D::random_unit_vector() + hit_record.normal();
-        if out_direction.near_zero() {
-            out_direction = hit_record.normal();
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        let frame = Onb::new(&hit_record.shading_normal());
+        let out_direction: Vector = frame.to_world(&CosineHemisphere.warp(sampler.get_2d()));
+        // A tilted shading normal must not send light through the surface.
+        if out_direction.dot(&hit_record.normal()) <= 0. {
+            return None;
         }
-        let reflection: Ray = Ray::new(hit_record.point(), out_direction);
+        let pdf: f64 = self.pdf(ray_in, hit_record, &out_direction);
+        let reflection: Ray = ray_in.scattered(hit_record.point(), out_direction);
+        let albedo: Color = self.albedo.evaluate(ray_in, hit_record);
+
+        Some(Scattering::new(reflection, albedo).with_pdf(pdf))
+    }
 
-        Some(Scattering::new(reflection, self.albedo))
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> Color {
+        self.albedo.evaluate(ray_in, hit_record) * reflected_cosine(hit_record, direction) / PI
+    }
+
+    fn pdf(&self, _ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> f64 {
+        reflected_cosine(hit_record, direction) / PI
+    }
+}
+
+/// Oren-Nayar rough diffuse reflection for matte surfaces such as clay, concrete
+/// or fabric. `sigma` is the standard deviation of the facet slopes in degrees;
+/// at zero it is identical to `Lambertian`.
+pub struct OrenNayar {
+    albedo: Arc<dyn Texture>,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Color, sigma: f64) -> Self {
+        OrenNayar::from_texture(Arc::new(SolidColor::new(albedo)), sigma)
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>, sigma: f64) -> Self {
+        let sigma_2: f64 = sigma.to_radians().powi(2);
+        OrenNayar {
+            albedo,
+            a: 1. - sigma_2 / (2. * (sigma_2 + 0.33)),
+            b: 0.45 * sigma_2 / (sigma_2 + 0.09),
+        }
+    }
+
+    /// Ratio of the Oren-Nayar response to the Lambertian one.
+    fn factor(&self, wo: &Vector, wi: &Vector) -> f64 {
+        let sine_o: f64 = (1. - wo.z().powi(2)).max(0.).sqrt();
+        let sine_i: f64 = (1. - wi.z().powi(2)).max(0.).sqrt();
+
+        let cosine_delta_phi: f64 = if sine_o > 1e-4 && sine_i > 1e-4 {
+            ((wo.x() * wi.x() + wo.y() * wi.y()) / (sine_o * sine_i)).max(0.)
+        } else {
+            0.
+        };
+
+        // sin(alpha) tan(beta), with alpha the larger and beta the smaller angle.
+        let (sine_alpha, tangent_beta) = if wi.z().abs() > wo.z().abs() {
+            (sine_o, sine_i / wi.z().abs())
+        } else {
+            (sine_i, sine_o / wo.z().abs().max(1e-8))
+        };
+
+        self.a + self.b * cosine_delta_phi * sine_alpha * tangent_beta
+    }
+}
+
+impl Material for OrenNayar {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        let frame = Onb::new(&hit_record.shading_normal());
+        let out_direction: Vector = frame.to_world(&CosineHemisphere.warp(sampler.get_2d()));
+        if out_direction.dot(&hit_record.normal()) <= 0. {
+            return None;
+        }
+
+        let wo: Vector = frame.to_local(&-ray_in.direction().normalize());
+        let wi: Vector = frame.to_local(&out_direction.normalize());
+        let albedo: Color = self.albedo.evaluate(ray_in, hit_record);
+        let pdf: f64 = self.pdf(ray_in, hit_record, &out_direction);
+        let reflection: Ray = ray_in.scattered(hit_record.point(), out_direction);
+
+        Some(Scattering::new(reflection, albedo * self.factor(&wo, &wi)).with_pdf(pdf))
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> Color {
+        let cosine: f64 = reflected_cosine(hit_record, direction);
+        if cosine <= 0. {
+            return Color::zeros();
+        }
+        let frame = Onb::new(&hit_record.shading_normal());
+        let wo: Vector = frame.to_local(&-ray_in.direction().normalize());
+        let wi: Vector = frame.to_local(&direction.normalize());
+        self.albedo.evaluate(ray_in, hit_record) * self.factor(&wo, &wi) * cosine / PI
+    }
+
+    fn pdf(&self, _ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> f64 {
+        reflected_cosine(hit_record, direction) / PI
     }
 }
 
@@ -66,58 +256,1166 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scattering> {
-        let out_direction: Vector = ray_in.direction().reflect(&hit_record.normal()).normalize()
-            + UniformUnitVec3D::random_unit_vector() * self.fuzz;
-        let reflection: Ray = Ray::new(hit_record.point(), out_direction);
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        let out_direction: Vector = ray_in
+            .direction()
+            .reflect(&hit_record.shading_normal())
+            .normalize()
+            + UniformSphere.warp(sampler.get_2d()) * self.fuzz;
+        // Only a perfect mirror keeps the footprint of the incoming ray coherent.
+        let differentials = (self.fuzz == 0.)
+            .then(|| hit_record.reflected_differentials(ray_in, &out_direction))
+            .flatten();
+        let reflection: Ray = ray_in
+            .scattered(hit_record.point(), out_direction)
+            .with_differentials(differentials);
         (out_direction.dot(&hit_record.normal()) > 0.)
-            .then_some(Scattering::new(reflection, self.albedo))
+            .then_some(Scattering::new(reflection, self.albedo).with_specular(self.fuzz == 0.))
+    }
+}
+
+/// Physically based metal: GGX microfacets with Smith shadowing and the Fresnel
+/// equations of a complex refractive index `eta + i k`, given per channel.
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    distribution: TrowbridgeReitz,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+        Conductor::anisotropic(eta, k, roughness, roughness)
+    }
+
+    /// Roughness may differ along the `u` and `v` directions of the surface.
+    pub fn anisotropic(eta: Color, k: Color, roughness_u: f64, roughness_v: f64) -> Self {
+        Conductor {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::from_roughness(roughness_u, roughness_v),
+        }
+    }
+
+    pub fn gold(roughness: f64) -> Self {
+        Conductor::new(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Conductor::new(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminum(roughness: f64) -> Self {
+        Conductor::new(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Conductor::new(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    /// Outgoing, incident and half vectors in the shading frame, when the rough
+    /// lobe can reflect towards `direction`.
+    fn local_directions(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: &Vector,
+    ) -> Option<(Vector, Vector, Vector)> {
+        if self.distribution.is_smooth() || reflected_cosine(hit_record, direction) <= 0. {
+            return None;
+        }
+        let frame = Onb::with_tangent(&hit_record.shading_normal(), &hit_record.dpdu());
+        let wo: Vector = frame.to_local(&-ray_in.direction().normalize());
+        let wi: Vector = frame.to_local(&direction.normalize());
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return None;
+        }
+        Some((wo, wi, (wo + wi).normalize()))
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        let frame = Onb::with_tangent(&hit_record.shading_normal(), &hit_record.dpdu());
+        let wo: Vector = frame.to_local(&-ray_in.direction().normalize());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let (wi, attenuation, pdf) = if self.distribution.is_smooth() {
+            let wi: Vector = Vector::new(-wo.x(), -wo.y(), wo.z());
+            (
+                wi,
+                microfacet::fresnel_conductor(wo.z(), &self.eta, &self.k),
+                None,
+            )
+        } else {
+            let wm: Vector = self.distribution.sample_wm(&wo, sampler.get_2d());
+            let wi: Vector = microfacet::reflect(&wo, &wm);
+            if wi.z() <= 0. {
+                return None;
+            }
+            // f cos / pdf for visible-normal sampling reduces to F G2 / G1.
+            let fresnel: Color = microfacet::fresnel_conductor(wo.dot(&wm), &self.eta, &self.k);
+            let shadowing: f64 = self.distribution.g(&wo, &wi) / self.distribution.g1(&wo);
+            let pdf: f64 = self.distribution.d_visible(&wo, &wm) / (4. * wo.dot(&wm));
+            (wi, fresnel * shadowing, Some(pdf))
+        };
+
+        let out_direction: Vector = frame.to_world(&wi);
+        if out_direction.dot(&hit_record.normal()) <= 0. {
+            return None;
+        }
+        let differentials = self
+            .distribution
+            .is_smooth()
+            .then(|| hit_record.reflected_differentials(ray_in, &out_direction))
+            .flatten();
+        let reflection: Ray = ray_in
+            .scattered(hit_record.point(), out_direction)
+            .with_differentials(differentials);
+
+        let scattering = Scattering::new(reflection, attenuation);
+        Some(match pdf {
+            Some(pdf) => scattering.with_pdf(pdf),
+            None => scattering.with_specular(true),
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> Color {
+        match self.local_directions(ray_in, hit_record, direction) {
+            Some((wo, wi, wm)) => {
+                microfacet::fresnel_conductor(wo.dot(&wm), &self.eta, &self.k)
+                    * self.distribution.d(&wm)
+                    * self.distribution.g(&wo, &wi)
+                    / (4. * wo.z())
+            }
+            None => Color::zeros(),
+        }
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> f64 {
+        match self.local_directions(ray_in, hit_record, direction) {
+            Some((wo, _, wm)) => self.distribution.d_visible(&wo, &wm) / (4. * wo.dot(&wm)),
+            None => 0.,
+        }
     }
 }
 
 pub struct Dielectric {
-    refractive_index: f64,
+    refractive_index: RefractiveIndex,
+    absorption: Color,
+    priority: u32,
 }
 
 impl Dielectric {
     pub fn new(refractive_index: f64) -> Self {
-        Dielectric { refractive_index }
+        Dielectric::dispersive(RefractiveIndex::Constant(refractive_index))
+    }
+
+    /// A dielectric whose refractive index depends on the wavelength of spectral
+    /// rays, splitting white light into colours.
+    pub fn dispersive(refractive_index: RefractiveIndex) -> Self {
+        Dielectric {
+            refractive_index,
+            absorption: Color::zeros(),
+            priority: 0,
+        }
+    }
+
+    /// Absorption coefficient per unit distance travelled inside the medium,
+    /// attenuating light by `exp(-absorption * distance)` (Beer-Lambert).
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    /// Where this medium overlaps another, the one with the higher priority
+    /// fills the overlap. Modelling liquid in a glass, for example, lets the
+    /// liquid slightly intersect a glass of higher priority.
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<Scattering> {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
         fn reflectance(cosine_theta: f64, ri: f64) -> f64 {
             let r0: f64 = ((1. - ri) / (1. + ri)).powi(2);
             r0 + (1. - r0) * (1. - cosine_theta).powi(5)
         }
 
-        let ri: f64 = if hit_record.front_face() {
-            1. / self.refractive_index
+        let refractive_index: f64 = self.refractive_index.at(ray_in.wavelength());
+        let boundary: MediumBoundary = medium_boundary(
+            ray_in,
+            hit_record,
+            refractive_index,
+            self.absorption,
+            self.priority,
+        );
+        if !boundary.is_interface {
+            return Some(passed_through(ray_in, hit_record, &boundary));
+        }
+
+        // Ratio of the refractive index on the incident side over the far side.
+        let ri: f64 = 1. / boundary.eta;
+
+        let unit_in: Vector = ray_in.direction().normalize();
+
+        let normal: Vector = hit_record.shading_normal();
+
+        let cosine_theta: f64 = (-unit_in.dot(&normal)).min(1.);
+
+        let sine_theta: f64 = (1. - cosine_theta.powi(2)).sqrt();
+
+        // Schlick's approximation
+        let reflected: bool =
+            ri * sine_theta > 1. || reflectance(cosine_theta, ri) > sampler.get_1d();
+        let out_direction: Vector = if reflected {
+            unit_in.reflect(&normal)
         } else {
-            self.refractive_index
+            unit_in.refract(&normal, ri)
+        };
+
+        // A perturbed shading normal can send the ray to the wrong side of the surface.
+        if reflected != (out_direction.dot(&hit_record.normal()) > 0.) {
+            return None;
+        }
+
+        let refraction: Ray = if reflected {
+            let differentials = hit_record.reflected_differentials(ray_in, &out_direction);
+            ray_in
+                .scattered(hit_record.point(), out_direction)
+                .with_differentials(differentials)
+        } else {
+            let differentials = hit_record.refracted_differentials(ray_in, &out_direction, ri);
+            ray_in
+                .scattered(hit_record.point(), out_direction)
+                .with_differentials(differentials)
+                .with_media(boundary.crossed)
+        };
+
+        // Absorption inside the medium is applied by the integrator.
+        let attenuation: Color = Color::new(1., 1., 1.);
+
+        Some(Scattering::new(refraction, attenuation).with_specular(true))
+    }
+}
+
+/// The boundary of the medium enclosed by the hit object, entered through its
+/// front faces and left through its back faces.
+fn medium_boundary(
+    ray_in: &Ray,
+    hit_record: &HitRecord,
+    refractive_index: f64,
+    absorption: Color,
+    priority: u32,
+) -> MediumBoundary {
+    let entry = MediumEntry::new(
+        hit_record.object_id(),
+        priority,
+        refractive_index,
+        absorption,
+    );
+    ray_in.media().boundary(entry, hit_record.front_face())
+}
+
+/// Continues `ray_in` unchanged across a surface that is not an interface.
+fn passed_through(ray_in: &Ray, hit_record: &HitRecord, boundary: &MediumBoundary) -> Scattering {
+    let passed: Ray = ray_in
+        .scattered(hit_record.point(), ray_in.direction())
+        .with_differentials(ray_in.differentials().copied())
+        .with_media(boundary.crossed);
+    Scattering::new(passed, Color::new(1., 1., 1.)).with_specular(true)
+}
+
+/// Rough glass following Walter et al., "Microfacet Models for Refraction
+/// through Rough Surfaces": GGX microfacets that either reflect or refract
+/// according to the exact dielectric Fresnel term.
+pub struct RoughDielectric {
+    refractive_index: f64,
+    distribution: TrowbridgeReitz,
+    priority: u32,
+}
+
+impl RoughDielectric {
+    pub fn new(refractive_index: f64, roughness: f64) -> Self {
+        RoughDielectric {
+            refractive_index,
+            distribution: TrowbridgeReitz::from_roughness(roughness, roughness),
+            priority: 0,
+        }
+    }
+
+    /// Priority of the enclosed medium where it overlaps others, as for `Dielectric`.
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        let frame = Onb::with_tangent(&hit_record.shading_normal(), &hit_record.dpdu());
+        let wo: Vector = frame.to_local(&-ray_in.direction().normalize());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let boundary: MediumBoundary = medium_boundary(
+            ray_in,
+            hit_record,
+            self.refractive_index,
+            Color::zeros(),
+            self.priority,
+        );
+        if !boundary.is_interface {
+            return Some(passed_through(ray_in, hit_record, &boundary));
+        }
+        let eta: f64 = boundary.eta;
+
+        let wm: Vector = if self.distribution.is_smooth() {
+            Vector::z()
+        } else {
+            self.distribution.sample_wm(&wo, sampler.get_2d())
+        };
+
+        // Choosing the lobe by Fresnel cancels it, leaving G2 / G1 for both lobes.
+        let fresnel: f64 = microfacet::fresnel_dielectric(wo.dot(&wm), eta);
+        let reflected: bool = sampler.get_1d() < fresnel;
+        let wi: Vector = if reflected {
+            microfacet::reflect(&wo, &wm)
+        } else {
+            microfacet::refract(&wo, &wm, eta)?
+        };
+        if reflected != (wi.z() > 0.) {
+            return None;
+        }
+
+        let out_direction: Vector = frame.to_world(&wi);
+        if reflected != (out_direction.dot(&hit_record.normal()) > 0.) {
+            return None;
+        }
+
+        let attenuation: f64 = if self.distribution.is_smooth() {
+            1.
+        } else {
+            self.distribution.g(&wo, &wi) / self.distribution.g1(&wo)
+        };
+
+        let differentials = if !self.distribution.is_smooth() {
+            None
+        } else if reflected {
+            hit_record.reflected_differentials(ray_in, &out_direction)
+        } else {
+            hit_record.refracted_differentials(ray_in, &out_direction, 1. / eta)
+        };
+        let scattered: Ray = ray_in
+            .scattered(hit_record.point(), out_direction)
+            .with_differentials(differentials);
+        let scattered: Ray = if reflected {
+            scattered
+        } else {
+            scattered.with_media(boundary.crossed)
+        };
+
+        Some(
+            Scattering::new(scattered, Color::repeat(attenuation))
+                .with_specular(self.distribution.is_smooth()),
+        )
+    }
+}
+
+/// Disney-style uber material. Diffuse, sheen, specular, clearcoat and
+/// transmission lobes are mixed by their parameters; one lobe is sampled per
+/// scattering event and weighted against the combined density of all lobes.
+pub struct Principled {
+    base_color: Arc<dyn Texture>,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+    sheen: f64,
+    clearcoat: f64,
+    transmission: f64,
+    anisotropy: f64,
+    priority: u32,
+}
+
+impl Principled {
+    pub fn new(base_color: Color) -> Self {
+        Principled::from_texture(Arc::new(SolidColor::new(base_color)))
+    }
+
+    pub fn from_texture(base_color: Arc<dyn Texture>) -> Self {
+        Principled {
+            base_color,
+            metallic: 0.,
+            roughness: 0.5,
+            specular: 0.5,
+            sheen: 0.,
+            clearcoat: 0.,
+            transmission: 0.,
+            anisotropy: 0.,
+            priority: 0,
+        }
+    }
+
+    pub fn with_metallic(mut self, metallic: f64) -> Self {
+        self.metallic = metallic.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: f64) -> Self {
+        self.roughness = roughness.clamp(0., 1.);
+        self
+    }
+
+    /// Dielectric reflectance at normal incidence is `0.08 * specular`.
+    pub fn with_specular(mut self, specular: f64) -> Self {
+        self.specular = specular.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: f64) -> Self {
+        self.sheen = sheen.max(0.);
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: f64) -> Self {
+        self.clearcoat = clearcoat.max(0.);
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: f64) -> Self {
+        self.transmission = transmission.clamp(0., 1.);
+        self
+    }
+
+    /// Stretches highlights along the `u` direction of the surface.
+    pub fn with_anisotropy(mut self, anisotropy: f64) -> Self {
+        self.anisotropy = anisotropy.clamp(0., 1.);
+        self
+    }
+
+    /// Priority of the transmissive interior where it overlaps other media, as
+    /// for `Dielectric`.
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    const CLEARCOAT_ALPHA: f64 = 0.01;
+
+    /// Whether any light refracts into the object.
+    fn is_transmissive(&self) -> bool {
+        (1. - self.metallic) * self.transmission > 0.
+    }
+
+    fn refractive_index(&self) -> f64 {
+        let r0: f64 = (0.08 * self.specular).sqrt().min(0.99);
+        (1. + r0) / (1. - r0)
+    }
+
+    fn medium_boundary(&self, ray_in: &Ray, hit_record: &HitRecord) -> MediumBoundary {
+        medium_boundary(
+            ray_in,
+            hit_record,
+            self.refractive_index(),
+            Color::zeros(),
+            self.priority,
+        )
+    }
+
+    fn specular_distribution(&self) -> TrowbridgeReitz {
+        let aspect: f64 = (1. - 0.9 * self.anisotropy).sqrt();
+        let alpha: f64 = self.roughness.powi(2);
+        TrowbridgeReitz::new((alpha / aspect).max(1e-3), (alpha * aspect).max(1e-3))
+    }
+
+    fn transmission_distribution(&self) -> TrowbridgeReitz {
+        TrowbridgeReitz::new(
+            self.roughness.powi(2).max(1e-3),
+            self.roughness.powi(2).max(1e-3),
+        )
+    }
+
+    fn clearcoat_distribution() -> TrowbridgeReitz {
+        TrowbridgeReitz::new(Self::CLEARCOAT_ALPHA, Self::CLEARCOAT_ALPHA)
+    }
+
+    /// Probabilities of sampling the diffuse, specular, clearcoat and transmission lobes.
+    fn lobe_probabilities(&self) -> [f64; 4] {
+        let weights: [f64; 4] = [
+            (1. - self.metallic) * (1. - self.transmission),
+            1.,
+            0.25 * self.clearcoat,
+            (1. - self.metallic) * self.transmission,
+        ];
+        let total: f64 = weights.iter().sum();
+        weights.map(|weight| weight / total)
+    }
+
+    /// `evaluate` for a world-space direction, on the side facing the ray unless
+    /// the back face bounds a transmissive interior.
+    fn evaluate_towards(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: &Vector,
+    ) -> (Color, f64) {
+        if !hit_record.front_face() && self.is_transmissive() {
+            return (Color::zeros(), 0.);
+        }
+        let frame = Onb::with_tangent(&hit_record.shading_normal(), &hit_record.dpdu());
+        let wo: Vector = frame.to_local(&-ray_in.direction().normalize());
+        let wi: Vector = frame.to_local(&direction.normalize());
+        if wo.z() <= 0. || (wi.z() > 0.) != (direction.dot(&hit_record.normal()) > 0.) {
+            return (Color::zeros(), 0.);
+        }
+        let base_color: Color = self.base_color.evaluate(ray_in, hit_record);
+        let eta: f64 = self.medium_boundary(ray_in, hit_record).eta;
+        self.evaluate(&base_color, &wo, &wi, eta)
+    }
+
+    /// Returns `f * |cos(wi)|` summed over all lobes, and the combined sampling
+    /// density. `eta` is the relative refractive index of the transmission lobe.
+    fn evaluate(&self, base_color: &Color, wo: &Vector, wi: &Vector, eta: f64) -> (Color, f64) {
+        let probabilities: [f64; 4] = self.lobe_probabilities();
+        let cosine_o: f64 = wo.z();
+        let cosine_i: f64 = wi.z();
+
+        let mut value: Color = Color::zeros();
+        let mut pdf: f64 = 0.;
+
+        if cosine_i > 0. {
+            let wm: Vector = (wo + wi).normalize();
+            let cosine_h: f64 = wi.dot(&wm);
+            let schlick: f64 = (1. - cosine_h).clamp(0., 1.).powi(5);
+
+            // Diffuse and sheen.
+            let diffuse_weight: f64 = (1. - self.metallic) * (1. - self.transmission);
+            let sheen: Color = Color::repeat(self.sheen * schlick);
+            value += diffuse_weight * (base_color / PI + sheen) * cosine_i;
+            pdf += probabilities[0] * cosine_i / PI;
+
+            // Specular, blending from a dielectric to a metallic Fresnel term.
+            let distribution: TrowbridgeReitz = self.specular_distribution();
+            let f0: Color = Color::repeat(0.08 * self.specular).lerp(base_color, self.metallic);
+            let fresnel: Color = f0 + (Color::repeat(1.) - f0) * schlick;
+            value += fresnel * distribution.d(&wm) * distribution.g(wo, wi) / (4. * cosine_o);
+            pdf += probabilities[1] * distribution.d_visible(wo, &wm) / (4. * wo.dot(&wm));
+
+            // Clearcoat, a fixed low-roughness dielectric layer.
+            let distribution: TrowbridgeReitz = Self::clearcoat_distribution();
+            let fresnel: f64 = 0.04 + 0.96 * schlick;
+            value += Color::repeat(
+                0.25 * self.clearcoat * fresnel * distribution.d(&wm) * distribution.g(wo, wi)
+                    / (4. * cosine_o),
+            );
+            pdf += probabilities[2] * distribution.d_visible(wo, &wm) / (4. * wo.dot(&wm));
+        } else if cosine_i < 0. && self.transmission > 0. {
+            // Rough transmission into the surface.
+            let wm: Vector = eta * wi + wo;
+            if wm.norm_squared() == 0. {
+                return (value, pdf);
+            }
+            let wm: Vector = wm.normalize() * wm.z().signum();
+            if wm.dot(wi) * cosine_i < 0. || wm.dot(wo) * cosine_o < 0. {
+                return (value, pdf);
+            }
+
+            let distribution: TrowbridgeReitz = self.transmission_distribution();
+            let denominator: f64 = (wi.dot(&wm) + wo.dot(&wm) / eta).powi(2);
+            let dwm_dwi: f64 = wi.dot(&wm).abs() / denominator;
+            let fresnel: f64 = microfacet::fresnel_dielectric(wo.dot(&wm), eta);
+
+            let transmission_weight: f64 = (1. - self.metallic) * self.transmission;
+            value += transmission_weight
+                * base_color
+                * ((1. - fresnel)
+                    * distribution.d(&wm)
+                    * distribution.g(wo, wi)
+                    * wo.dot(&wm).abs()
+                    * dwm_dwi
+                    / cosine_o);
+            pdf += probabilities[3] * distribution.d_visible(wo, &wm) * dwm_dwi;
+        }
+
+        (value, pdf)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        // Inside a transmissive object only the glass interface remains. Opaque
+        // back faces, e.g. of open geometry, shade like front faces.
+        if !hit_record.front_face() && self.is_transmissive() {
+            return RoughDielectric::new(self.refractive_index(), self.roughness)
+                .with_priority(self.priority)
+                .scatter(ray_in, hit_record, sampler);
+        }
+
+        let boundary: MediumBoundary = self.medium_boundary(ray_in, hit_record);
+        if self.is_transmissive() && !boundary.is_interface {
+            return Some(passed_through(ray_in, hit_record, &boundary));
+        }
+
+        let frame = Onb::with_tangent(&hit_record.shading_normal(), &hit_record.dpdu());
+        let wo: Vector = frame.to_local(&-ray_in.direction().normalize());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let probabilities: [f64; 4] = self.lobe_probabilities();
+        let mut choice: f64 = sampler.get_1d();
+        let u: (f64, f64) = sampler.get_2d();
+        let lobe: usize = probabilities
+            .iter()
+            .position(|probability| {
+                choice -= probability;
+                choice < 0.
+            })
+            .unwrap_or(1);
+
+        let wi: Vector = match lobe {
+            0 => CosineHemisphere.warp(u),
+            1 => microfacet::reflect(&wo, &self.specular_distribution().sample_wm(&wo, u)),
+            2 => microfacet::reflect(&wo, &Self::clearcoat_distribution().sample_wm(&wo, u)),
+            _ => {
+                let wm: Vector = self.transmission_distribution().sample_wm(&wo, u);
+                microfacet::refract(&wo, &wm, boundary.eta)?
+            }
         };
 
+        let out_direction: Vector = frame.to_world(&wi);
+        if (wi.z() > 0.) != (out_direction.dot(&hit_record.normal()) > 0.) {
+            return None;
+        }
+
+        let base_color: Color = self.base_color.evaluate(ray_in, hit_record);
+        let (value, pdf) = self.evaluate(&base_color, &wo, &wi, boundary.eta);
+        if pdf <= 0. {
+            return None;
+        }
+
+        let scattered: Ray = ray_in.scattered(hit_record.point(), out_direction);
+        let scattered: Ray = if wi.z() < 0. {
+            scattered.with_media(boundary.crossed)
+        } else {
+            scattered
+        };
+        Some(Scattering::new(scattered, value / pdf).with_pdf(pdf))
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> Color {
+        self.evaluate_towards(ray_in, hit_record, direction).0
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> f64 {
+        self.evaluate_towards(ray_in, hit_record, direction).1
+    }
+}
+
+/// Thin interference coating, such as a soap film or an anti-reflective layer,
+/// on top of another material. The film replaces the Fresnel reflectance of
+/// `base`, which should describe the bare substrate of index `substrate_ior`:
+/// light `base` reflects is reweighted by the coated over the bare reflectance,
+/// and light it transmits by the matching ratio of transmittances. A film of
+/// zero thickness leaves `base` unchanged.
+pub struct ThinFilm {
+    base: Arc<dyn Material>,
+    thickness: f64,
+    film_ior: f64,
+    substrate_ior: Complex<f64>,
+}
+
+impl ThinFilm {
+    /// `thickness` is in nanometres; `substrate_ior` is complex for metals.
+    pub fn new(
+        base: Arc<dyn Material>,
+        thickness: f64,
+        film_ior: f64,
+        substrate_ior: Complex<f64>,
+    ) -> Self {
+        ThinFilm {
+            base,
+            thickness,
+            film_ior,
+            substrate_ior,
+        }
+    }
+
+    /// Representative wavelengths of the red, green and blue channels.
+    const RGB_WAVELENGTHS: [f64; 3] = [630., 532., 465.];
+
+    fn reflectance(&self, cosine: f64, thickness: f64, wavelength: Option<f64>) -> Color {
+        let at = |wavelength: f64| -> f64 {
+            microfacet::fresnel_thin_film(
+                cosine,
+                wavelength,
+                thickness,
+                self.film_ior,
+                self.substrate_ior,
+            )
+        };
+        match wavelength {
+            Some(wavelength) => Color::repeat(at(wavelength)),
+            None => Color::from(Self::RGB_WAVELENGTHS.map(at)),
+        }
+    }
+
+    /// Cosine of the incident direction on the air side of the film. Rays hitting
+    /// the back face come from inside a dielectric substrate and are refracted
+    /// out by Snell's law; `None` when they are totally internally reflected.
+    fn outside_cosine(&self, hit_record: &HitRecord, unit_in: &Vector) -> Option<f64> {
+        let cosine: f64 = (-unit_in.dot(&hit_record.shading_normal())).clamp(0., 1.);
+        if hit_record.front_face() {
+            return Some(cosine);
+        }
+        let sine_2: f64 = (1. - cosine.powi(2)) * self.substrate_ior.re.powi(2);
+        (sine_2 < 1.).then(|| (1. - sine_2).sqrt())
+    }
+}
+
+impl Material for ThinFilm {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        let scattering: Scattering = self.base.scatter(ray_in, hit_record, sampler)?;
+
         let unit_in: Vector = ray_in.direction().normalize();
+        let Some(cosine) = self.outside_cosine(hit_record, &unit_in) else {
+            return Some(scattering.without_pdf());
+        };
+        let coated: Color = self.reflectance(cosine, self.thickness, ray_in.wavelength());
+        let bare: Color = self.reflectance(cosine, 0., ray_in.wavelength());
 
-        let cosine_theta: f64 = (-unit_in.dot(&hit_record.normal())).min(1.);
+        let reflected: bool = scattering.ray().direction().dot(&hit_record.normal()) > 0.;
+        let weight: Color = if reflected {
+            coated.component_div(&bare.map(|bare| bare.max(1e-6)))
+        } else {
+            (Color::repeat(1.) - coated)
+                .component_div(&(Color::repeat(1.) - bare).map(|bare| bare.max(1e-6)))
+        };
+        // The ratio grows without bound where the bare surface barely reflects,
+        // so cap it to keep the scattered light from exceeding the incident light.
+        let limit: Color = scattering
+            .attenuation()
+            .map(|attenuation| 1. / attenuation.max(1e-6));
 
-        let sine_theta: f64 = (1. - cosine_theta.powi(2)).sqrt();
+        Some(scattering.scaled(weight.inf(&limit)).without_pdf())
+    }
 
-        let uniform = rand_distr::Uniform::new(0., 1.).unwrap();
+    fn is_opaque(&self, ray: &Ray, hit_record: &HitRecord) -> bool {
+        self.base.is_opaque(ray, hit_record)
+    }
+}
 
-        // Schlick's approximation
-        let out_direction: Vector = if ri * sine_theta > 1.
-            || reflectance(cosine_theta, ri) > uniform.sample(&mut rand::rng())
+/// Blends two materials, picking `second` with probability `weight` at each hit.
+pub struct MixMaterial {
+    first: Arc<dyn Material>,
+    second: Arc<dyn Material>,
+    weight: Arc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(first: Arc<dyn Material>, second: Arc<dyn Material>, weight: f64) -> Self {
+        MixMaterial::from_texture(
+            first,
+            second,
+            Arc::new(SolidColor::new(Color::repeat(weight))),
+        )
+    }
+
+    /// The weight is the mean of the texture channels, clamped to `[0, 1]`.
+    pub fn from_texture(
+        first: Arc<dyn Material>,
+        second: Arc<dyn Material>,
+        weight: Arc<dyn Texture>,
+    ) -> Self {
+        MixMaterial {
+            first,
+            second,
+            weight,
+        }
+    }
+}
+
+impl MixMaterial {
+    fn weight(&self, ray: &Ray, hit_record: &HitRecord) -> f64 {
+        self.weight.evaluate(ray, hit_record).mean().clamp(0., 1.)
+    }
+
+    /// The child shading this hit. Hashing the ray makes `is_opaque` and
+    /// `scatter` agree on the choice.
+    fn chosen(&self, ray: &Ray, hit_record: &HitRecord) -> &Arc<dyn Material> {
+        let salt: u64 = self as *const Self as u64;
+        if hash_float(ray, hit_record.t(), salt) < self.weight(ray, hit_record) {
+            &self.second
+        } else {
+            &self.first
+        }
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        let scattering: Scattering = self
+            .chosen(ray_in, hit_record)
+            .scatter(ray_in, hit_record, sampler)?;
+        if scattering.pdf().is_none() {
+            return Some(scattering);
+        }
+
+        // Directions either child can evaluate are weighted by the blend of both.
+        let direction: Vector = scattering.ray().direction();
+        let pdf: f64 = self.pdf(ray_in, hit_record, &direction);
+        if pdf <= 0. {
+            return None;
+        }
+        let attenuation: Color = self.eval(ray_in, hit_record, &direction) / pdf;
+        Some(Scattering::new(scattering.ray().clone(), attenuation).with_pdf(pdf))
+    }
+
+    fn is_opaque(&self, ray: &Ray, hit_record: &HitRecord) -> bool {
+        self.chosen(ray, hit_record).is_opaque(ray, hit_record)
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        let weight: f64 = self.weight(ray_in, hit_record);
+        (1. - weight) * self.first.emitted(ray_in, hit_record)
+            + weight * self.second.emitted(ray_in, hit_record)
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> Color {
+        let weight: f64 = self.weight(ray_in, hit_record);
+        (1. - weight) * self.first.eval(ray_in, hit_record, direction)
+            + weight * self.second.eval(ray_in, hit_record, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> f64 {
+        let weight: f64 = self.weight(ray_in, hit_record);
+        (1. - weight) * self.first.pdf(ray_in, hit_record, direction)
+            + weight * self.second.pdf(ray_in, hit_record, direction)
+    }
+}
+
+/// Clear dielectric coat over any base material, such as varnish or car paint.
+/// The coat reflects specularly by its Fresnel term and hands the rest of the
+/// light to `base`.
+pub struct Coated {
+    base: Arc<dyn Material>,
+    refractive_index: f64,
+}
+
+impl Coated {
+    pub fn new(base: Arc<dyn Material>, refractive_index: f64) -> Self {
+        Coated {
+            base,
+            refractive_index,
+        }
+    }
+}
+
+impl Material for Coated {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        let unit_in: Vector = ray_in.direction().normalize();
+        let normal: Vector = hit_record.shading_normal();
+        let cosine_theta: f64 = (-unit_in.dot(&normal)).min(1.);
+        let eta: f64 = if hit_record.front_face() {
+            self.refractive_index
+        } else {
+            1. / self.refractive_index
+        };
+
+        if sampler.get_1d() >= microfacet::fresnel_dielectric(cosine_theta, eta) {
+            let scattering: Scattering = self.base.scatter(ray_in, hit_record, sampler)?;
+            // Light the base reflects leaves through the coat, which reflects
+            // part of it back down.
+            let out_direction: Vector = scattering.ray().direction().normalize();
+            let cosine_out: f64 = out_direction.dot(&normal);
+            let transmitted: f64 = if out_direction.dot(&hit_record.normal()) > 0. {
+                1. - microfacet::fresnel_dielectric(cosine_out.max(0.), eta)
+            } else {
+                1.
+            };
+            return Some(scattering.scaled(Color::repeat(transmitted)).without_pdf());
+        }
+
+        let out_direction: Vector = unit_in.reflect(&normal);
+        if out_direction.dot(&hit_record.normal()) <= 0. {
+            return None;
+        }
+        let differentials = hit_record.reflected_differentials(ray_in, &out_direction);
+        let reflection: Ray = ray_in
+            .scattered(hit_record.point(), out_direction)
+            .with_differentials(differentials);
+
+        Some(Scattering::new(reflection, Color::new(1., 1., 1.)).with_specular(true))
+    }
+
+    fn is_opaque(&self, ray: &Ray, hit_record: &HitRecord) -> bool {
+        self.base.is_opaque(ray, hit_record)
+    }
+}
+
+/// Random-walk subsurface scattering for skin, wax or marble. Light refracts
+/// into a closed object, then scatters isotropically through a homogeneous
+/// medium until it leaves through the boundary again. Each step of the walk is
+/// one scattering event, so long walks need a generous maximum depth.
+pub struct Subsurface {
+    medium: HomogeneousMedium,
+    refractive_index: f64,
+    priority: u32,
+}
+
+impl Subsurface {
+    /// `albedo` is the single-scattering albedo and `mean_free_path` the average
+    /// distance between scattering events, both per colour channel.
+    pub fn new(albedo: Color, mean_free_path: Color, refractive_index: f64) -> Self {
+        Subsurface {
+            medium: HomogeneousMedium::from_albedo(albedo, mean_free_path),
+            refractive_index,
+            priority: 0,
+        }
+    }
+
+    /// Priority of the interior where it overlaps other media, as for `Dielectric`.
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Reflects off or refracts through the boundary by its Fresnel term.
+    fn cross_boundary(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        weight: Color,
+        sampler: &mut dyn Sampler,
+    ) -> Scattering {
+        // The walk itself scatters inside, so the medium carries no absorption.
+        let boundary: MediumBoundary = medium_boundary(
+            ray_in,
+            hit_record,
+            self.refractive_index,
+            Color::zeros(),
+            self.priority,
+        );
+        if !boundary.is_interface {
+            return passed_through(ray_in, hit_record, &boundary).scaled(weight);
+        }
+
+        let wo: Vector = -ray_in.direction().normalize();
+        let normal: Vector = hit_record.shading_normal();
+        let fresnel: f64 = microfacet::fresnel_dielectric(wo.dot(&normal), boundary.eta);
+        let scattered: Ray = match microfacet::refract(&wo, &normal, boundary.eta) {
+            Some(refracted) if sampler.get_1d() >= fresnel => ray_in
+                .scattered(hit_record.point(), refracted)
+                .with_media(boundary.crossed),
+            _ => ray_in.scattered(hit_record.point(), microfacet::reflect(&wo, &normal)),
+        };
+
+        Scattering::new(scattered, weight).with_specular(true)
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        if hit_record.front_face() {
+            return Some(self.cross_boundary(ray_in, hit_record, Color::repeat(1.), sampler));
+        }
+
+        // The ray travelled inside the object; it may have scattered on the way.
+        let length: f64 = ray_in.direction().norm();
+        match self
+            .medium
+            .sample(sampler.get_2d(), hit_record.t() * length)
         {
-            unit_in.reflect(&hit_record.normal())
+            FreeFlight::Scattered { distance, weight } => {
+                let origin = ray_in.origin() + ray_in.direction() / length * distance;
+                let out_direction: Vector = UniformSphere.warp(sampler.get_2d());
+                Some(Scattering::new(
+                    ray_in.scattered(origin, out_direction),
+                    weight,
+                ))
+            }
+            FreeFlight::Escaped { weight } => {
+                Some(self.cross_boundary(ray_in, hit_record, weight, sampler))
+            }
+        }
+    }
+}
+
+/// Area light emitting `emit` from the front faces of its object.
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        DiffuseLight::from_texture(Arc::new(SolidColor::new(emit)))
+    }
+
+    pub fn from_texture(emit: Arc<dyn Texture>) -> Self {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray_in: &Ray,
+        _hit_record: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        None
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        if hit_record.front_face() {
+            self.emit.evaluate(ray_in, hit_record)
         } else {
-            unit_in.refract(&hit_record.normal(), ri)
+            Color::zeros()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AlphaMode {
+    /// Hits with alpha below the threshold are cut out.
+    Threshold(f64),
+    /// Hits are kept with probability alpha, giving partial transparency.
+    Stochastic,
+}
+
+/// Cuts holes into a base material using the mean of an alpha texture, for
+/// foliage, fences and similar geometry.
+pub struct AlphaMask {
+    base: Arc<dyn Material>,
+    alpha: Arc<dyn Texture>,
+    mode: AlphaMode,
+}
+
+impl AlphaMask {
+    pub fn new(base: Arc<dyn Material>, alpha: Arc<dyn Texture>, mode: AlphaMode) -> Self {
+        AlphaMask { base, alpha, mode }
+    }
+}
+
+impl Material for AlphaMask {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Scattering> {
+        self.base.scatter(ray_in, hit_record, sampler)
+    }
+
+    fn is_opaque(&self, ray: &Ray, hit_record: &HitRecord) -> bool {
+        let (u, v) = hit_record.uv();
+        let alpha: f64 = self.alpha.value(u, v, &hit_record.point()).mean();
+        let visible: bool = match self.mode {
+            AlphaMode::Threshold(threshold) => alpha >= threshold,
+            // Hashing the ray keeps the decision stable for a given ray.
+            AlphaMode::Stochastic => alpha >= 1. || hash_float(ray, hit_record.t(), 0) < alpha,
         };
+        visible && self.base.is_opaque(ray, hit_record)
+    }
 
-        let refraction: Ray = Ray::new(hit_record.point(), out_direction);
-        let attenuation: Color = Color::new(1., 1., 1.);
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        self.base.emitted(ray_in, hit_record)
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> Color {
+        self.base.eval(ray_in, hit_record, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector) -> f64 {
+        self.base.pdf(ray_in, hit_record, direction)
+    }
+}
 
-        Some(Scattering::new(refraction, attenuation))
+/// Uniform value in `[0, 1)` derived from a ray and a hit distance. Different
+/// `salt`s give independent values for the same hit.
+fn hash_float(ray: &Ray, t: f64, salt: u64) -> f64 {
+    let mut hash: u64 = 0x9e37_79b9_7f4a_7c15 ^ salt;
+    let origin = ray.origin();
+    let direction: Vector = ray.direction();
+    for value in [
+        origin.x,
+        origin.y,
+        origin.z,
+        direction.x,
+        direction.y,
+        direction.z,
+        t,
+    ] {
+        // SplitMix64 finalizer.
+        hash = (hash ^ value.to_bits()).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        hash ^= hash >> 31;
     }
+    (hash >> 11) as f64 / (1_u64 << 53) as f64
 }
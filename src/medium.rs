@@ -0,0 +1,213 @@
+use crate::{INFINITY, color::Color};
+
+/// Participating medium with constant coefficients, given per colour channel.
+#[derive(Debug, Clone, Copy)]
+pub struct HomogeneousMedium {
+    sigma_a: Color,
+    sigma_s: Color,
+}
+
+/// Outcome of sampling how far light travels through a medium.
+#[derive(Debug, Clone, Copy)]
+pub enum FreeFlight {
+    /// Light scattered after `distance`; `weight` already includes `sigma_s`.
+    Scattered { distance: f64, weight: Color },
+    /// Light reached the end of the segment unscattered.
+    Escaped { weight: Color },
+}
+
+impl HomogeneousMedium {
+    pub fn new(sigma_a: Color, sigma_s: Color) -> Self {
+        HomogeneousMedium { sigma_a, sigma_s }
+    }
+
+    /// A medium with single-scattering `albedo` and a `mean_free_path` per channel.
+    pub fn from_albedo(albedo: Color, mean_free_path: Color) -> Self {
+        let sigma_t: Color = mean_free_path.map(|distance| 1. / distance.max(1e-8));
+        HomogeneousMedium::new(
+            (Color::repeat(1.) - albedo).component_mul(&sigma_t),
+            albedo.component_mul(&sigma_t),
+        )
+    }
+
+    pub fn sigma_a(&self) -> Color {
+        self.sigma_a
+    }
+
+    pub fn sigma_s(&self) -> Color {
+        self.sigma_s
+    }
+
+    pub fn sigma_t(&self) -> Color {
+        self.sigma_a + self.sigma_s
+    }
+
+    pub fn transmittance(&self, distance: f64) -> Color {
+        (-self.sigma_t() * distance).map(f64::exp)
+    }
+
+    /// Samples a free-flight distance along a segment of length `max_distance`.
+    /// The distance follows one channel picked by `u.0`, and the weight divides by
+    /// the density averaged over all channels so chromatic media stay unbiased.
+    pub fn sample(&self, u: (f64, f64), max_distance: f64) -> FreeFlight {
+        let sigma_t: Color = self.sigma_t();
+        let channel: usize = ((u.0 * 3.) as usize).min(2);
+        let distance: f64 = if sigma_t[channel] > 0. {
+            -(1. - u.1).ln() / sigma_t[channel]
+        } else {
+            INFINITY
+        };
+        if distance < max_distance {
+            let transmittance: Color = self.transmittance(distance);
+            let pdf: f64 = sigma_t.component_mul(&transmittance).mean();
+            FreeFlight::Scattered {
+                distance,
+                weight: self.sigma_s.component_mul(&transmittance) / pdf,
+            }
+        } else {
+            let transmittance: Color = self.transmittance(max_distance);
+            FreeFlight::Escaped {
+                weight: transmittance / transmittance.mean(),
+            }
+        }
+    }
+}
+
+/// A medium a ray is currently inside of, identified by the object that bounds it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MediumEntry {
+    id: usize,
+    priority: u32,
+    refractive_index: f64,
+    absorption: Color,
+}
+
+impl MediumEntry {
+    pub fn new(id: usize, priority: u32, refractive_index: f64, absorption: Color) -> Self {
+        MediumEntry {
+            id,
+            priority,
+            refractive_index,
+            absorption,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    pub fn refractive_index(&self) -> f64 {
+        self.refractive_index
+    }
+}
+
+/// Media enclosing a ray, for nested dielectrics such as liquid in a glass.
+/// Where volumes overlap, the entry with the highest priority (the most recently
+/// entered among equals) defines the medium; interfaces of the others are ignored.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MediumStack {
+    entries: [Option<MediumEntry>; MediumStack::CAPACITY],
+}
+
+impl MediumStack {
+    /// Deeper nesting than this is ignored.
+    pub const CAPACITY: usize = 8;
+
+    pub fn new() -> Self {
+        MediumStack::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(Option::is_none)
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.entries.iter().flatten().any(|entry| entry.id == id)
+    }
+
+    /// The medium the ray is travelling through, if any.
+    pub fn current(&self) -> Option<&MediumEntry> {
+        self.entries
+            .iter()
+            .flatten()
+            .max_by_key(|entry| entry.priority)
+    }
+
+    /// Refractive index of the current medium, or of vacuum outside of all media.
+    pub fn refractive_index(&self) -> f64 {
+        self.current().map_or(1., MediumEntry::refractive_index)
+    }
+
+    /// Beer-Lambert transmittance of the current medium over `distance`.
+    pub fn transmittance(&self, distance: f64) -> Color {
+        match self.current() {
+            Some(entry) => (-entry.absorption * distance).map(f64::exp),
+            None => Color::repeat(1.),
+        }
+    }
+
+    pub fn pushed(mut self, entry: MediumEntry) -> Self {
+        if let Some(slot) = self.entries.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(entry);
+        }
+        self
+    }
+
+    pub fn removed(mut self, id: usize) -> Self {
+        if let Some(position) = self
+            .entries
+            .iter()
+            .rposition(|slot| slot.is_some_and(|entry| entry.id == id))
+        {
+            self.entries[position..].rotate_left(1);
+            self.entries[Self::CAPACITY - 1] = None;
+        }
+        self
+    }
+
+    /// Crossing the boundary of the medium `entry`, into it when `entering`
+    /// and out of it otherwise.
+    pub fn boundary(&self, entry: MediumEntry, entering: bool) -> MediumBoundary {
+        let crossed: MediumStack = if entering {
+            self.pushed(entry)
+        } else {
+            self.removed(entry.id)
+        };
+
+        // Surfaces of media overlapped by one of higher priority are not interfaces.
+        let is_interface: bool = if entering {
+            self.current()
+                .is_none_or(|current| current.priority <= entry.priority)
+        } else {
+            !self.contains(entry.id) || self.current().is_some_and(|current| current.id == entry.id)
+        };
+
+        let eta: f64 = if entering {
+            entry.refractive_index / self.refractive_index()
+        } else {
+            crossed.refractive_index() / entry.refractive_index
+        };
+
+        MediumBoundary {
+            crossed,
+            is_interface,
+            eta,
+        }
+    }
+}
+
+/// What a ray meets at the boundary of a medium.
+#[derive(Debug, Clone, Copy)]
+pub struct MediumBoundary {
+    /// Media enclosing the ray once it has crossed the boundary.
+    pub crossed: MediumStack,
+    /// Whether the boundary separates two media, rather than lying inside a
+    /// medium of higher priority that the ray should pass straight through.
+    pub is_interface: bool,
+    /// Ratio of the refractive index on the far side over the incident side.
+    pub eta: f64,
+}
@@ -0,0 +1,171 @@
+use nalgebra::Complex;
+
+use crate::{PI, color::Color, distribution::GgxVndf, vector::Vector};
+
+/// Trowbridge-Reitz (GGX) microfacet distribution in a local shading frame
+/// whose `z` axis is the surface normal.
+#[derive(Debug, Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        TrowbridgeReitz { alpha_x, alpha_y }
+    }
+
+    /// Maps a perceptual roughness in `[0, 1]` to `alpha = roughness²`.
+    pub fn from_roughness(roughness_x: f64, roughness_y: f64) -> Self {
+        TrowbridgeReitz::new(
+            roughness_x.clamp(0., 1.).powi(2),
+            roughness_y.clamp(0., 1.).powi(2),
+        )
+    }
+
+    pub fn alpha_x(&self) -> f64 {
+        self.alpha_x
+    }
+
+    pub fn alpha_y(&self) -> f64 {
+        self.alpha_y
+    }
+
+    /// Below this roughness the distribution is treated as a perfect mirror.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    pub fn d(&self, wm: &Vector) -> f64 {
+        let cosine_2: f64 = wm.z.powi(2);
+        if cosine_2 < 1e-16 {
+            return 0.;
+        }
+        let e: f64 = ((wm.x / self.alpha_x).powi(2) + (wm.y / self.alpha_y).powi(2)) / cosine_2;
+        1. / (PI * self.alpha_x * self.alpha_y * cosine_2.powi(2) * (1. + e).powi(2))
+    }
+
+    pub fn lambda(&self, w: &Vector) -> f64 {
+        let cosine_2: f64 = w.z.powi(2);
+        if cosine_2 < 1e-16 {
+            return 0.;
+        }
+        let alpha_2_tan_2: f64 =
+            ((self.alpha_x * w.x).powi(2) + (self.alpha_y * w.y).powi(2)) / cosine_2;
+        ((1. + alpha_2_tan_2).sqrt() - 1.) / 2.
+    }
+
+    pub fn g1(&self, w: &Vector) -> f64 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// Height-correlated Smith masking-shadowing.
+    pub fn g(&self, wo: &Vector, wi: &Vector) -> f64 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Distribution of normals visible from `w`, on the side of the surface
+    /// facing `w`.
+    pub fn d_visible(&self, w: &Vector, wm: &Vector) -> f64 {
+        if w.z.abs() < 1e-16 || w.z * wm.z <= 0. {
+            return 0.;
+        }
+        self.g1(w) / w.z.abs() * self.d(wm) * w.dot(wm).max(0.)
+    }
+
+    /// Samples a visible normal as seen from `w`.
+    pub fn sample_wm(&self, w: &Vector, u: (f64, f64)) -> Vector {
+        GgxVndf::new(*self, *w).warp(u)
+    }
+}
+
+/// Mirrors `w` about the microfacet normal `wm`.
+pub fn reflect(w: &Vector, wm: &Vector) -> Vector {
+    -w + 2. * w.dot(wm) * wm
+}
+
+/// Refracts `w` through the microfacet normal `wm`, where `eta` is the ratio of
+/// the transmitted over the incident refractive index. `w` and `wm` must lie in
+/// the same hemisphere.
+pub fn refract(w: &Vector, wm: &Vector, eta: f64) -> Option<Vector> {
+    let cosine_i: f64 = w.dot(wm);
+    let sine_2_t: f64 = (1. - cosine_i.powi(2)).max(0.) / eta.powi(2);
+    if sine_2_t >= 1. {
+        return None;
+    }
+    let cosine_t: f64 = (1. - sine_2_t).sqrt();
+    Some(-w / eta + (cosine_i / eta - cosine_t) * wm)
+}
+
+/// Unpolarized Fresnel reflectance of a dielectric interface, where `eta` is the
+/// ratio of the transmitted over the incident refractive index.
+pub fn fresnel_dielectric(cosine_i: f64, eta: f64) -> f64 {
+    let (cosine_i, eta) = if cosine_i < 0. {
+        (-cosine_i, 1. / eta)
+    } else {
+        (cosine_i, eta)
+    };
+    let cosine_i: f64 = cosine_i.min(1.);
+    let sine_2_t: f64 = (1. - cosine_i.powi(2)) / eta.powi(2);
+    if sine_2_t >= 1. {
+        return 1.;
+    }
+    let cosine_t: f64 = (1. - sine_2_t).sqrt();
+
+    let r_parallel: f64 = (eta * cosine_i - cosine_t) / (eta * cosine_i + cosine_t);
+    let r_perpendicular: f64 = (cosine_i - eta * cosine_t) / (cosine_i + eta * cosine_t);
+    (r_parallel.powi(2) + r_perpendicular.powi(2)) / 2.
+}
+
+/// Fresnel reflectance of a conductor with complex refractive index `eta + i k`.
+pub fn fresnel_complex(cosine_i: f64, eta: Complex<f64>) -> f64 {
+    let cosine_i: f64 = cosine_i.clamp(0., 1.);
+    let sine_2_i: f64 = 1. - cosine_i.powi(2);
+    let sine_2_t: Complex<f64> = Complex::new(sine_2_i, 0.) / (eta * eta);
+    let cosine_t: Complex<f64> = (Complex::new(1., 0.) - sine_2_t).sqrt();
+
+    let r_parallel: Complex<f64> = (eta * cosine_i - cosine_t) / (eta * cosine_i + cosine_t);
+    let r_perpendicular: Complex<f64> = (-eta * cosine_t + cosine_i) / (eta * cosine_t + cosine_i);
+    (r_parallel.norm_sqr() + r_perpendicular.norm_sqr()) / 2.
+}
+
+/// Per-channel conductor Fresnel reflectance.
+pub fn fresnel_conductor(cosine_i: f64, eta: &Color, k: &Color) -> Color {
+    Color::from_fn(|i, _| fresnel_complex(cosine_i, Complex::new(eta[i], k[i])))
+}
+
+/// Reflectance of a thin film of `film_ior` and `thickness` nanometres lying on a
+/// substrate of complex index `substrate_ior`, seen from air at `wavelength`
+/// nanometres. Accounts for interference between all internal reflections
+/// (Airy summation), averaged over both polarizations.
+pub fn fresnel_thin_film(
+    cosine_i: f64,
+    wavelength: f64,
+    thickness: f64,
+    film_ior: f64,
+    substrate_ior: Complex<f64>,
+) -> f64 {
+    let one: Complex<f64> = Complex::new(1., 0.);
+    let n1: Complex<f64> = one;
+    let n2: Complex<f64> = Complex::new(film_ior, 0.);
+    let n3: Complex<f64> = substrate_ior;
+
+    let cosine_1: Complex<f64> = Complex::new(cosine_i.clamp(0., 1.), 0.);
+    let sine_2_1: Complex<f64> = one - cosine_1 * cosine_1;
+    let cosine_2: Complex<f64> = (one - sine_2_1 / (n2 * n2)).sqrt();
+    let cosine_3: Complex<f64> = (one - sine_2_1 / (n3 * n3)).sqrt();
+
+    let phase: Complex<f64> = Complex::new(0., 4. * PI * thickness / wavelength) * n2 * cosine_2;
+    let shift: Complex<f64> = phase.exp();
+
+    let airy = |r_12: Complex<f64>, r_23: Complex<f64>| -> f64 {
+        ((r_12 + r_23 * shift) / (one + r_12 * r_23 * shift)).norm_sqr()
+    };
+
+    let s_12 = (n1 * cosine_1 - n2 * cosine_2) / (n1 * cosine_1 + n2 * cosine_2);
+    let s_23 = (n2 * cosine_2 - n3 * cosine_3) / (n2 * cosine_2 + n3 * cosine_3);
+    let p_12 = (n2 * cosine_1 - n1 * cosine_2) / (n2 * cosine_1 + n1 * cosine_2);
+    let p_23 = (n3 * cosine_2 - n2 * cosine_3) / (n3 * cosine_2 + n2 * cosine_3);
+
+    ((airy(s_12, s_23) + airy(p_12, p_23)) / 2.).clamp(0., 1.)
+}
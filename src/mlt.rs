@@ -0,0 +1,319 @@
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use rand_distr::StandardNormal;
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    hittable::HittableList,
+    integrator::Integrator,
+    interval::Interval,
+    sampler::{ONE_MINUS_EPSILON, Sampler, hash},
+};
+
+#[derive(Clone, Copy, Default)]
+struct PrimarySample {
+    value: f64,
+    last_modified: u64,
+    value_backup: f64,
+    modified_backup: u64,
+}
+
+/// Sampler over primary sample space for Metropolis light transport. Every
+/// dimension is a coordinate of a point in the unit hypercube that is mutated
+/// lazily: a large step replaces it with a fresh uniform value, a small step
+/// perturbs it by a Gaussian that wraps around, and a rejected proposal
+/// restores every dimension it touched.
+///
+/// The first two dimensions are the position on a film of `film` pixels.
+#[derive(Clone)]
+pub struct MltSampler {
+    film: (i32, i32),
+    sigma: f64,
+    large_step_probability: f64,
+    rng: SmallRng,
+    samples: Vec<PrimarySample>,
+    iteration: u64,
+    large_step: bool,
+    last_large_step: u64,
+    dimension: usize,
+    position: (f64, f64),
+}
+
+impl MltSampler {
+    pub fn new(film: (i32, i32), sigma: f64, large_step_probability: f64) -> Self {
+        MltSampler {
+            film,
+            sigma,
+            large_step_probability,
+            rng: SmallRng::seed_from_u64(0),
+            samples: Vec::new(),
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            dimension: 0,
+            position: (0., 0.),
+        }
+    }
+
+    /// Proposes a mutation of the current sample.
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.random::<f64>() < self.large_step_probability;
+    }
+
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    pub fn reject(&mut self) {
+        for sample in &mut self.samples {
+            if sample.last_modified == self.iteration {
+                sample.value = sample.value_backup;
+                sample.last_modified = sample.modified_backup;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    /// Pixel of the film the current sample lands in.
+    pub fn pixel(&self) -> (i32, i32) {
+        (
+            self.position.0.trunc() as i32,
+            self.position.1.trunc() as i32,
+        )
+    }
+
+    /// Brings dimension `index` up to the current iteration and returns it.
+    fn value(&mut self, index: usize) -> f64 {
+        if index >= self.samples.len() {
+            self.samples.resize(index + 1, PrimarySample::default());
+        }
+        let sample: &mut PrimarySample = &mut self.samples[index];
+
+        // Catch up on a large step missed while the dimension was unused.
+        if sample.last_modified < self.last_large_step {
+            sample.value = self.rng.random();
+            sample.last_modified = self.last_large_step;
+        }
+
+        sample.value_backup = sample.value;
+        sample.modified_backup = sample.last_modified;
+        if self.large_step {
+            sample.value = self.rng.random();
+        } else {
+            // Small steps skipped while unused add up to one wider step.
+            let steps: f64 = (self.iteration - sample.last_modified) as f64;
+            let normal: f64 = self.rng.sample(StandardNormal);
+            sample.value += normal * self.sigma * steps.sqrt();
+            sample.value = (sample.value - sample.value.floor()).min(ONE_MINUS_EPSILON);
+        }
+        sample.last_modified = self.iteration;
+        sample.value
+    }
+}
+
+impl Sampler for MltSampler {
+    fn samples_per_pixel(&self) -> u32 {
+        1
+    }
+
+    /// Takes the film position from the first two dimensions. The pixel and
+    /// index are ignored, as the film position is part of the sample.
+    fn start_pixel_sample(&mut self, _pixel: (i32, i32), _index: u32) {
+        self.position = (
+            self.value(0) * f64::from(self.film.0),
+            self.value(1) * f64::from(self.film.1),
+        );
+        self.dimension = 2;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.dimension += 1;
+        self.value(self.dimension - 1)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+
+    /// Position within the pixel returned by `pixel`.
+    fn get_pixel_2d(&mut self) -> (f64, f64) {
+        (self.position.0.fract(), self.position.1.fract())
+    }
+
+    /// Restarts the chain from a fresh sample drawn from `seed`.
+    fn set_seed(&mut self, seed: u64) {
+        *self = MltSampler::new(self.film, self.sigma, self.large_step_probability);
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+/// Primary sample space Metropolis light transport (Kelemen et al. 2002),
+/// driving an integrator through an `MltSampler`.
+///
+/// Bootstrap samples estimate the brightness of the image and pick the
+/// starting states of the Markov chains in proportion to their luminance. Each
+/// chain then proposes mutations, splatting both the current and the proposed
+/// sample weighted by their acceptance probability, so pixels end up with
+/// samples in proportion to their brightness.
+pub struct MltRenderer {
+    mutations_per_pixel: u32,
+    bootstrap_samples: u32,
+    chains: u32,
+    sigma: f64,
+    large_step_probability: f64,
+}
+
+impl MltRenderer {
+    pub fn new(mutations_per_pixel: u32) -> Self {
+        MltRenderer {
+            mutations_per_pixel,
+            bootstrap_samples: 100_000,
+            chains: 16,
+            sigma: 0.01,
+            large_step_probability: 0.3,
+        }
+    }
+
+    pub fn with_bootstrap_samples(mut self, bootstrap_samples: u32) -> Self {
+        self.bootstrap_samples = bootstrap_samples.max(1);
+        self
+    }
+
+    /// Chains run in parallel, each splatting to a film of its own.
+    pub fn with_chains(mut self, chains: u32) -> Self {
+        self.chains = chains.max(1);
+        self
+    }
+
+    /// Standard deviation of small steps, and the probability of a large step
+    /// replacing the whole sample instead.
+    pub fn with_mutations(mut self, sigma: f64, large_step_probability: f64) -> Self {
+        self.sigma = sigma;
+        self.large_step_probability = large_step_probability.clamp(0., 1.);
+        self
+    }
+
+    /// Linear pixel colours, row by row from the top left, seen by `camera`.
+    /// Only the camera's seed and spectral sampling are used.
+    pub fn render(
+        &self,
+        camera: &Camera,
+        world: &HittableList,
+        integrator: &dyn Integrator,
+        time: Interval,
+    ) -> Vec<Color> {
+        use indicatif::{ParallelProgressIterator, ProgressStyle};
+        use log::info;
+        use rayon::prelude::*;
+
+        integrator.prepare(world, time, camera.is_spectral(), camera.seed());
+
+        let film: (i32, i32) = (camera.image_width(), camera.image_height());
+        let pixels: usize = (film.0 * film.1) as usize;
+        let sampler = MltSampler::new(film, self.sigma, self.large_step_probability);
+
+        let evaluate = |sampler: &mut MltSampler| -> ((i32, i32), Color) {
+            sampler.start_pixel_sample((0, 0), 0);
+            let (i, j) = sampler.pixel();
+            let radiance: Color = camera.sample(i, j, world, integrator, time, sampler);
+            ((i, j), radiance)
+        };
+        let stream = |index: u32| -> MltSampler {
+            let mut sampler = sampler.clone();
+            sampler.set_seed(hash(&[camera.seed(), u64::from(index)]));
+            sampler
+        };
+
+        let weights: Vec<f64> = (0..self.bootstrap_samples)
+            .into_par_iter()
+            .map(|index| luminance(&evaluate(&mut stream(index)).1))
+            .collect();
+        let brightness: f64 = weights.iter().sum::<f64>() / weights.len() as f64;
+        info!("Bootstrap brightness: {brightness:.4}.");
+        if brightness <= 0. {
+            return vec![Color::zeros(); pixels];
+        }
+        let cdf: Vec<f64> = weights
+            .iter()
+            .scan(0., |total, weight| {
+                *total += weight;
+                Some(*total)
+            })
+            .collect();
+
+        let style = ProgressStyle::with_template(
+            "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {human_pos}/{human_len} ({eta})",
+        ).unwrap().progress_chars("#>-");
+
+        let total_mutations: u64 = u64::from(self.mutations_per_pixel) * pixels as u64;
+        let films: Vec<Vec<Color>> = (0..self.chains)
+            .into_par_iter()
+            .progress_with_style(style)
+            .map(|chain| {
+                let chains: u64 = u64::from(self.chains);
+                let chain: u64 = u64::from(chain);
+                let mutations: u64 =
+                    total_mutations * (chain + 1) / chains - total_mutations * chain / chains;
+                let mut rng = SmallRng::seed_from_u64(hash(&[camera.seed(), u64::MAX, chain]));
+
+                // Start from a bootstrap sample chosen by its luminance.
+                let target: f64 = rng.random::<f64>() * cdf[cdf.len() - 1];
+                let start: usize = cdf.partition_point(|total| *total <= target);
+                let mut sampler: MltSampler = stream(start.min(cdf.len() - 1) as u32);
+                let (mut pixel, mut current) = evaluate(&mut sampler);
+
+                let index = |(i, j): (i32, i32)| (j * film.0 + i) as usize;
+                let mut splats: Vec<Color> = vec![Color::zeros(); pixels];
+                for _ in 0..mutations {
+                    sampler.start_iteration();
+                    let (proposed_pixel, proposed) = evaluate(&mut sampler);
+
+                    let current_luminance: f64 = luminance(&current);
+                    let proposed_luminance: f64 = luminance(&proposed);
+                    let accept: f64 = match current_luminance > 0. {
+                        true => (proposed_luminance / current_luminance).min(1.),
+                        false => 1.,
+                    };
+                    if accept > 0. {
+                        splats[index(proposed_pixel)] += proposed * accept / proposed_luminance;
+                    }
+                    if current_luminance > 0. {
+                        splats[index(pixel)] += current * (1. - accept) / current_luminance;
+                    }
+
+                    if rng.random::<f64>() < accept {
+                        pixel = proposed_pixel;
+                        current = proposed;
+                        sampler.accept();
+                    } else {
+                        sampler.reject();
+                    }
+                }
+                splats
+            })
+            .collect();
+
+        let scale: f64 = brightness / f64::from(self.mutations_per_pixel);
+        films
+            .into_iter()
+            .fold(vec![Color::zeros(); pixels], |image, splats| {
+                image
+                    .into_iter()
+                    .zip(splats)
+                    .map(|(pixel, splats)| pixel + splats * scale)
+                    .collect()
+            })
+    }
+}
+
+/// Scalar contribution that the chains sample in proportion to.
+fn luminance(color: &Color) -> f64 {
+    (0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z).max(0.)
+}
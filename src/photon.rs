@@ -0,0 +1,493 @@
+use std::{cmp::Ordering, collections::BinaryHeap, ops::Range, sync::Arc, sync::RwLock};
+
+use crate::{
+    PI,
+    color::Color,
+    distribution::CosineHemisphere,
+    hittable::{HitRecord, Hittable, HittableList},
+    integrator::{Integrator, IntegratorConfig, sky_box},
+    interval::Interval,
+    ray::Ray,
+    sampler::{IndependentSampler, Sampler, hash},
+    spectrum,
+    vector::{Onb, Point, Vector},
+};
+
+/// Light flux deposited on a surface.
+#[derive(Debug, Clone)]
+pub struct Photon {
+    point: Point,
+    direction: Vector,
+    power: Color,
+    bounces: i32,
+}
+
+impl Photon {
+    pub fn new(point: Point, direction: Vector, power: Color) -> Self {
+        Photon {
+            point,
+            direction,
+            power,
+            bounces: 0,
+        }
+    }
+
+    /// Number of surfaces the photon was scattered by before it landed.
+    pub fn with_bounces(mut self, bounces: i32) -> Self {
+        self.bounces = bounces;
+        self
+    }
+
+    pub fn point(&self) -> Point {
+        self.point
+    }
+
+    /// Unit direction the photon was travelling in when it landed.
+    pub fn direction(&self) -> Vector {
+        self.direction
+    }
+
+    pub fn power(&self) -> Color {
+        self.power
+    }
+
+    pub fn bounces(&self) -> i32 {
+        self.bounces
+    }
+}
+
+/// Photons stored as a balanced kd-tree: every slice of the array has its
+/// median along `axes` in the middle, with the smaller half before it.
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<usize>,
+}
+
+/// A photon found by a query, ordered by distance for the max-heap.
+struct Neighbour {
+    distance_2: f64,
+    index: usize,
+}
+
+impl PartialEq for Neighbour {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Neighbour {}
+
+impl PartialOrd for Neighbour {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbour {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance_2.total_cmp(&other.distance_2)
+    }
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes: Vec<usize> = vec![0; photons.len()];
+        Self::balance(&mut photons, &mut axes);
+        PhotonMap { photons, axes }
+    }
+
+    /// Splits along the axis of largest extent at the median, then recurses
+    /// into both halves.
+    fn balance(photons: &mut [Photon], axes: &mut [usize]) {
+        if photons.is_empty() {
+            return;
+        }
+        let (min, max) = photons.iter().fold(
+            (
+                Point::from(Vector::repeat(f64::MAX)),
+                Point::from(Vector::repeat(f64::MIN)),
+            ),
+            |(min, max), photon| (min.inf(&photon.point), max.sup(&photon.point)),
+        );
+        let axis: usize = (max - min).imax();
+
+        let median: usize = photons.len() / 2;
+        photons.select_nth_unstable_by(median, |a, b| a.point[axis].total_cmp(&b.point[axis]));
+        axes[median] = axis;
+
+        let (photons_below, photons_above) = photons.split_at_mut(median);
+        let (axes_below, axes_above) = axes.split_at_mut(median);
+        Self::balance(photons_below, axes_below);
+        Self::balance(&mut photons_above[1..], &mut axes_above[1..]);
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    /// Up to `count` photons closest to `point` within `max_radius`, and the
+    /// squared radius of the disc they were gathered from: the distance to the
+    /// farthest one when `count` were found, `max_radius` otherwise.
+    pub fn nearest(&self, point: &Point, count: usize, max_radius: f64) -> (Vec<&Photon>, f64) {
+        let mut heap: BinaryHeap<Neighbour> = BinaryHeap::with_capacity(count + 1);
+        let mut radius_2: f64 = max_radius.powi(2);
+        if count > 0 {
+            self.gather(
+                0..self.photons.len(),
+                point,
+                count,
+                &mut radius_2,
+                &mut heap,
+            );
+        }
+        if heap.len() < count {
+            radius_2 = max_radius.powi(2);
+        }
+        let photons: Vec<&Photon> = heap
+            .into_iter()
+            .map(|neighbour| &self.photons[neighbour.index])
+            .collect();
+        (photons, radius_2)
+    }
+
+    fn gather(
+        &self,
+        range: Range<usize>,
+        point: &Point,
+        count: usize,
+        radius_2: &mut f64,
+        heap: &mut BinaryHeap<Neighbour>,
+    ) {
+        if range.is_empty() {
+            return;
+        }
+        let median: usize = range.start + range.len() / 2;
+        let photon: &Photon = &self.photons[median];
+        let axis: usize = self.axes[median];
+
+        let delta: f64 = point[axis] - photon.point[axis];
+        let (near, far) = if delta < 0. {
+            (range.start..median, median + 1..range.end)
+        } else {
+            (median + 1..range.end, range.start..median)
+        };
+
+        self.gather(near, point, count, radius_2, heap);
+
+        let distance_2: f64 = (photon.point - point).norm_squared();
+        if distance_2 < *radius_2 {
+            heap.push(Neighbour {
+                distance_2,
+                index: median,
+            });
+            if heap.len() > count {
+                heap.pop();
+            }
+            if heap.len() == count {
+                *radius_2 = heap.peek().unwrap().distance_2;
+            }
+        }
+
+        if delta.powi(2) < *radius_2 {
+            self.gather(far, point, count, radius_2, heap);
+        }
+    }
+}
+
+/// Path tracer whose caustics come from a photon map. Photons are shot from
+/// the lights, and from the sky towards the caustic casters, and stored where
+/// they land on a surface with an evaluable material after one or more
+/// specular bounces. Camera paths read the map at those surfaces and ignore
+/// the same light when they reach it through specular bounces.
+///
+/// The map is traced again by `prepare` at the start of every render, one
+/// wavelength per photon when rendering spectrally. Lights are the list given
+/// to the config with `with_lights`.
+pub struct PhotonIntegrator {
+    config: IntegratorConfig,
+    photon_count: usize,
+    caustic_casters: Option<Arc<HittableList>>,
+    gather_count: usize,
+    gather_radius: f64,
+    caustic_map: RwLock<PhotonMap>,
+}
+
+impl PhotonIntegrator {
+    pub fn new(config: IntegratorConfig, photon_count: usize) -> Self {
+        PhotonIntegrator {
+            config,
+            photon_count,
+            caustic_casters: None,
+            gather_count: 50,
+            gather_radius: 0.25,
+            caustic_map: RwLock::new(PhotonMap::new(Vec::new())),
+        }
+    }
+
+    /// Specular objects that focus sky light; sky photons are aimed at them.
+    pub fn with_caustic_casters(mut self, caustic_casters: Arc<HittableList>) -> Self {
+        self.caustic_casters = Some(caustic_casters).filter(|casters| !casters.is_empty());
+        self
+    }
+
+    /// Estimates caustics from the `count` nearest photons, searching no
+    /// farther than `max_radius`.
+    pub fn with_gather(mut self, count: usize, max_radius: f64) -> Self {
+        self.gather_count = count.max(1);
+        self.gather_radius = max_radius;
+        self
+    }
+
+    fn lights(&self) -> Option<&HittableList> {
+        self.config
+            .lights()
+            .map(Arc::as_ref)
+            .filter(|lights| !lights.is_empty())
+    }
+
+    /// Starts a photon from a light or from the sky, each carrying its share
+    /// of the flux, and follows it to where it lands. Spectral photons carry a
+    /// sampled wavelength and land with its RGB estimate.
+    fn emit_photon(
+        &self,
+        world: &HittableList,
+        time: Interval,
+        spectral: bool,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Photon> {
+        let sources: f64 = f64::from(u8::from(self.lights().is_some()))
+            + f64::from(u8::from(self.caustic_casters.is_some()));
+        let scale: f64 = sources / self.photon_count as f64;
+
+        let use_lights: bool = match (self.lights(), &self.caustic_casters) {
+            (Some(_), Some(_)) => sampler.get_1d() < 0.5,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => return None,
+        };
+
+        let (ray, power): (Ray, Color) = if use_lights {
+            let (hit_record, pdf) = self.lights()?.sample_surface(sampler)?;
+            let direction: Vector =
+                Onb::new(&hit_record.normal()).to_world(&CosineHemisphere.warp(sampler.get_2d()));
+            let emitted: Color = hit_record.material().emitted(
+                &Ray::new(hit_record.point() + direction, -direction),
+                &hit_record,
+            );
+            let ray: Ray = Ray::new(hit_record.point(), direction);
+            (ray, emitted * PI / pdf)
+        } else {
+            // Sky light arriving at a point of a caster, from a cosine-weighted
+            // direction above it, when nothing else blocks that direction.
+            let (hit_record, pdf) = self.caustic_casters.as_ref()?.sample_surface(sampler)?;
+            let towards_sky: Vector =
+                Onb::new(&hit_record.normal()).to_world(&CosineHemisphere.warp(sampler.get_2d()));
+            let sky: Ray = Ray::new(hit_record.point(), towards_sky);
+            if world.hit(&sky, time).is_some() {
+                return None;
+            }
+            let ray: Ray = Ray::new(hit_record.point() + towards_sky, -towards_sky);
+            (ray, sky_box(&sky) * PI / pdf)
+        };
+        if !power.iter().all(|channel| channel.is_finite()) || power.max() <= 0. {
+            return None;
+        }
+
+        let wavelength: Option<f64> =
+            spectral.then(|| spectrum::sample_wavelength(sampler.get_1d()));
+        self.trace_photon(
+            ray.with_wavelength(wavelength),
+            spectrum::project(power * scale, wavelength),
+            world,
+            time,
+            sampler,
+        )
+    }
+
+    fn trace_photon(
+        &self,
+        mut ray: Ray,
+        mut power: Color,
+        world: &HittableList,
+        time: Interval,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Photon> {
+        let wavelength: Option<f64> = ray.wavelength();
+        for bounces in 0..self.config.max_depth() {
+            let hit_record: HitRecord = world.hit(&ray, time)?;
+            let transmittance: Color = ray
+                .media()
+                .transmittance(hit_record.t() * ray.direction().norm());
+            power.component_mul_assign(&spectrum::project(transmittance, wavelength));
+
+            let scattering = hit_record.material().scatter(&ray, &hit_record, sampler)?;
+            if scattering.pdf().is_some() {
+                if bounces == 0 {
+                    return None;
+                }
+                let power: Color = match wavelength {
+                    Some(wavelength) => {
+                        spectrum::spectral_to_rgb(power.x, wavelength, spectrum::wavelength_pdf())
+                    }
+                    None => power,
+                };
+                let direction: Vector = ray.direction().normalize();
+                return Some(
+                    Photon::new(hit_record.point(), direction, power).with_bounces(bounces),
+                );
+            }
+            // Camera paths carry light through glossy lobes themselves.
+            if !scattering.is_specular() {
+                return None;
+            }
+
+            power.component_mul_assign(&spectrum::project(scattering.attenuation(), wavelength));
+            ray = scattering.ray().clone();
+        }
+        None
+    }
+
+    /// Radiance reflected towards `ray` by the photons around the hit that
+    /// bounced at most `max_bounces` times, keeping paths within `max_depth`.
+    fn caustics(
+        &self,
+        map: &PhotonMap,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        max_bounces: i32,
+    ) -> Color {
+        let (photons, radius_2) =
+            map.nearest(&hit_record.point(), self.gather_count, self.gather_radius);
+        let material = hit_record.material();
+        let reflected: Color = photons
+            .iter()
+            .filter(|photon| photon.bounces() <= max_bounces)
+            .filter_map(|photon| {
+                let incoming: Vector = -photon.direction();
+                let cosine: f64 = incoming.dot(&hit_record.shading_normal());
+                (cosine > 0.).then(|| {
+                    (material.eval(ray, hit_record, &incoming) / cosine)
+                        .component_mul(&photon.power())
+                })
+            })
+            .sum();
+        reflected / (PI * radius_2)
+    }
+
+    /// Whether light reached at `hit_record` is already carried by photons,
+    /// having been found through specular bounces after an evaluable surface.
+    fn photon_source(&self, hit_record: &HitRecord) -> bool {
+        self.lights()
+            .is_some_and(|lights| lights.surface_pdf(&hit_record.point()) > 0.)
+    }
+
+    fn caustic_caster(&self, hit_record: &HitRecord) -> bool {
+        self.caustic_casters
+            .as_ref()
+            .is_some_and(|casters| casters.surface_pdf(&hit_record.point()) > 0.)
+    }
+}
+
+impl Integrator for PhotonIntegrator {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        time: Interval,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let map = self.caustic_map.read().unwrap();
+
+        let wavelength: Option<f64> = ray.wavelength();
+        let mut radiance: Color = Color::zeros();
+        let mut throughput: Color = Color::repeat(1.);
+        let mut ray: Ray = ray.clone();
+
+        // Set once an evaluable surface is followed by specular bounces, and
+        // cleared at the next surface that is not specular.
+        let mut caustic: bool = false;
+        let mut seen_evaluable: bool = false;
+        let mut last_caster: bool = false;
+
+        for depth in 0..self.config.max_depth() {
+            let Some(hit_record) = world.hit(&ray, time) else {
+                if !(caustic && last_caster) {
+                    radiance +=
+                        throughput.component_mul(&spectrum::project(sky_box(&ray), wavelength));
+                }
+                break;
+            };
+
+            let transmittance: Color = ray
+                .media()
+                .transmittance(hit_record.t() * ray.direction().norm());
+            throughput.component_mul_assign(&spectrum::project(transmittance, wavelength));
+
+            let material = hit_record.material();
+            if !(caustic && self.photon_source(&hit_record)) {
+                let emitted: Color = material.emitted(&ray, &hit_record);
+                radiance += throughput.component_mul(&spectrum::project(emitted, wavelength));
+            }
+
+            let Some(scattering) = material.scatter(&ray, &hit_record, sampler) else {
+                break;
+            };
+            if scattering.pdf().is_some() {
+                // The light is hit one surface after the photon's last bounce.
+                let max_bounces: i32 = self.config.max_depth() - depth - 2;
+                let caustics: Color = self.caustics(&map, &ray, &hit_record, max_bounces);
+                radiance += throughput.component_mul(&spectrum::project(caustics, wavelength));
+                seen_evaluable = true;
+                caustic = false;
+            } else if scattering.is_specular() {
+                caustic = seen_evaluable;
+                last_caster = self.caustic_caster(&hit_record);
+            } else {
+                // Photons stop at glossy lobes that cannot be evaluated.
+                seen_evaluable = false;
+                caustic = false;
+            }
+
+            let Some((next, weight)) = self.config.next_ray(&ray, &hit_record, scattering, sampler)
+            else {
+                break;
+            };
+            throughput.component_mul_assign(&spectrum::project(weight, wavelength));
+            ray = next;
+        }
+
+        self.config.clamped(radiance)
+    }
+
+    /// Traces the caustic photon map for `world`, replacing any earlier one.
+    /// Photons are shot in batches, each seeded from `seed` and its index.
+    fn prepare(&self, world: &HittableList, time: Interval, spectral: bool, seed: u64) {
+        use log::info;
+        use rayon::prelude::*;
+
+        const BATCH: usize = 4096;
+
+        let photons: Vec<Photon> = (0..self.photon_count.div_ceil(BATCH))
+            .into_par_iter()
+            .flat_map_iter(|batch| {
+                let mut sampler = IndependentSampler::new(1);
+                sampler.set_seed(hash(&[seed, batch as u64]));
+                let count: usize = BATCH.min(self.photon_count - batch * BATCH);
+                (0..count as u32).filter_map(move |index| {
+                    sampler.start_pixel_sample((0, 0), index);
+                    self.emit_photon(world, time, spectral, &mut sampler)
+                })
+            })
+            .collect();
+        info!(
+            "Stored {} caustic photons out of {}.",
+            photons.len(),
+            self.photon_count
+        );
+        *self.caustic_map.write().unwrap() = PhotonMap::new(photons);
+    }
+}
@@ -1,15 +1,52 @@
-use crate::vector::{Point, Vector};
+use crate::{
+    medium::MediumStack,
+    vector::{Point, Vector},
+};
 
 extern crate nalgebra as na;
 
+#[derive(Debug, Clone)]
 pub struct Ray {
     origin: Point,
     direction: Vector,
+    differentials: Option<RayDifferentials>,
+    wavelength: Option<f64>,
+    media: MediumStack,
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Vector) -> Self {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            differentials: None,
+            wavelength: None,
+            media: MediumStack::new(),
+        }
+    }
+
+    /// A new ray leaving a surface, keeping the wavelength and enclosing media
+    /// of this one.
+    pub fn scattered(&self, origin: Point, direction: Vector) -> Self {
+        Ray::new(origin, direction)
+            .with_wavelength(self.wavelength)
+            .with_media(self.media)
+    }
+
+    pub fn with_media(mut self, media: MediumStack) -> Self {
+        self.media = media;
+        self
+    }
+
+    /// Wavelength in nanometres carried by spectral rays.
+    pub fn with_wavelength(mut self, wavelength: Option<f64>) -> Self {
+        self.wavelength = wavelength;
+        self
+    }
+
+    pub fn with_differentials(mut self, differentials: Option<RayDifferentials>) -> Self {
+        self.differentials = differentials;
+        self
     }
 
     pub fn origin(&self) -> Point {
@@ -20,7 +57,71 @@ impl Ray {
         self.direction
     }
 
+    pub fn differentials(&self) -> Option<&RayDifferentials> {
+        self.differentials.as_ref()
+    }
+
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
+    /// Media the ray travels through, innermost last.
+    pub fn media(&self) -> &MediumStack {
+        &self.media
+    }
+
     pub fn at(&self, t: f64) -> Point {
         self.origin + t * self.direction
     }
 }
+
+/// Auxiliary rays offset by one pixel in x and y, used to estimate texture footprints.
+#[derive(Debug, Clone, Copy)]
+pub struct RayDifferentials {
+    rx_origin: Point,
+    rx_direction: Vector,
+    ry_origin: Point,
+    ry_direction: Vector,
+}
+
+impl RayDifferentials {
+    pub fn new(
+        rx_origin: Point,
+        rx_direction: Vector,
+        ry_origin: Point,
+        ry_direction: Vector,
+    ) -> Self {
+        RayDifferentials {
+            rx_origin,
+            rx_direction,
+            ry_origin,
+            ry_direction,
+        }
+    }
+
+    pub fn rx_origin(&self) -> Point {
+        self.rx_origin
+    }
+
+    pub fn rx_direction(&self) -> Vector {
+        self.rx_direction
+    }
+
+    pub fn ry_origin(&self) -> Point {
+        self.ry_origin
+    }
+
+    pub fn ry_direction(&self) -> Vector {
+        self.ry_direction
+    }
+
+    /// Shrinks the offsets towards the main ray, e.g. by `1 / sqrt(samples_per_pixel)`.
+    pub fn scaled(&self, ray: &Ray, scale: f64) -> Self {
+        RayDifferentials {
+            rx_origin: ray.origin() + (self.rx_origin - ray.origin()) * scale,
+            rx_direction: ray.direction() + (self.rx_direction - ray.direction()) * scale,
+            ry_origin: ray.origin() + (self.ry_origin - ray.origin()) * scale,
+            ry_direction: ray.direction() + (self.ry_direction - ray.direction()) * scale,
+        }
+    }
+}
@@ -0,0 +1,624 @@
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use std::sync::OnceLock;
+
+/// Largest value below one, so that samples stay in `[0, 1)`.
+pub const ONE_MINUS_EPSILON: f64 = 1. - f64::EPSILON / 2.;
+
+/// Source of the uniform samples consumed while tracing one camera sample.
+/// Each call takes the next dimension of the current sample; samplers that
+/// stratify do so per dimension across the samples of a pixel, so callers
+/// should request dimensions in the same order for every sample.
+pub trait Sampler: Send + Sync {
+    fn samples_per_pixel(&self) -> u32;
+
+    /// Starts sample `index` of `pixel`, restarting from the first dimension.
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32);
+
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> (f64, f64);
+
+    /// Position of the sample within the pixel, taken first in every sample.
+    fn get_pixel_2d(&mut self) -> (f64, f64) {
+        self.get_2d()
+    }
+
+    /// Makes every value a function of the seed, pixel, sample index and
+    /// dimension alone, so renders are reproducible whatever the thread that
+    /// traces each pixel.
+    fn set_seed(&mut self, seed: u64);
+
+    /// A sampler with the same configuration, for use on another thread.
+    fn boxed_clone(&self) -> Box<dyn Sampler>;
+}
+
+/// Uniform random samples with no stratification.
+#[derive(Clone)]
+pub struct IndependentSampler {
+    seed: u64,
+    samples_per_pixel: u32,
+    rng: SmallRng,
+}
+
+impl IndependentSampler {
+    pub fn new(samples_per_pixel: u32) -> Self {
+        IndependentSampler {
+            seed: 0,
+            samples_per_pixel,
+            rng: SmallRng::seed_from_u64(0),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.rng = sample_stream(self.seed, pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.random()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        self.rng.random()
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+/// Divides every dimension into one stratum per sample, or a grid of
+/// `x_samples` by `y_samples` strata for 2D samples. Strata are visited in a
+/// different random order for each pixel and dimension.
+#[derive(Clone)]
+pub struct StratifiedSampler {
+    seed: u64,
+    x_samples: u32,
+    y_samples: u32,
+    jitter: bool,
+    pixel: (i32, i32),
+    index: u32,
+    dimension: u32,
+    rng: SmallRng,
+}
+
+impl StratifiedSampler {
+    /// Without `jitter`, samples sit at the centres of their strata.
+    pub fn new(x_samples: u32, y_samples: u32, jitter: bool) -> Self {
+        StratifiedSampler {
+            seed: 0,
+            x_samples: x_samples.max(1),
+            y_samples: y_samples.max(1),
+            jitter,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            rng: SmallRng::seed_from_u64(0),
+        }
+    }
+
+    fn offset(&mut self) -> f64 {
+        if self.jitter { self.rng.random() } else { 0.5 }
+    }
+
+    fn stratum(&mut self) -> u32 {
+        let hash: u64 = hash(&[self.seed, pixel_key(self.pixel), u64::from(self.dimension)]);
+        permutation_element(self.index, self.samples_per_pixel(), hash as u32)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn samples_per_pixel(&self) -> u32 {
+        self.x_samples * self.y_samples
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+        self.rng = sample_stream(self.seed, pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let stratum: u32 = self.stratum();
+        self.dimension += 1;
+        ((f64::from(stratum) + self.offset()) / f64::from(self.samples_per_pixel()))
+            .min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let stratum: u32 = self.stratum();
+        self.dimension += 2;
+        let (x, y) = (stratum % self.x_samples, stratum / self.x_samples);
+        (
+            ((f64::from(x) + self.offset()) / f64::from(self.x_samples)).min(ONE_MINUS_EPSILON),
+            ((f64::from(y) + self.offset()) / f64::from(self.y_samples)).min(ONE_MINUS_EPSILON),
+        )
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+/// Halton sequence with a prime base per dimension. Digits are Owen scrambled
+/// with a different permutation for each pixel, so pixels do not share
+/// patterns. Past the tabulated primes, dimensions reuse bases with new
+/// scrambles.
+#[derive(Clone)]
+pub struct HaltonSampler {
+    seed: u64,
+    samples_per_pixel: u32,
+    pixel: (i32, i32),
+    index: u32,
+    dimension: u32,
+}
+
+impl HaltonSampler {
+    pub fn new(samples_per_pixel: u32) -> Self {
+        HaltonSampler {
+            seed: 0,
+            samples_per_pixel,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    const PRIMES: [u64; 64] = [
+        2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89,
+        97, 101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181,
+        191, 193, 197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281,
+        283, 293, 307, 311,
+    ];
+
+    fn sample_dimension(&mut self) -> f64 {
+        let base: u64 = Self::PRIMES[self.dimension as usize % Self::PRIMES.len()];
+        let hash: u64 = hash(&[self.seed, pixel_key(self.pixel), u64::from(self.dimension)]);
+        self.dimension += 1;
+        owen_scrambled_radical_inverse(base, u64::from(self.index), hash as u32)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.sample_dimension()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.sample_dimension(), self.sample_dimension())
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+/// Padded Sobol samples: every 1D or 2D request takes the first one or two
+/// Sobol dimensions, with the sample order shuffled and the points Owen
+/// scrambled independently per pixel and dimension. Works best with a power
+/// of two samples per pixel.
+#[derive(Clone)]
+pub struct SobolSampler {
+    seed: u64,
+    samples_per_pixel: u32,
+    pixel: (i32, i32),
+    index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: u32) -> Self {
+        SobolSampler {
+            seed: 0,
+            samples_per_pixel,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Shuffled index into the pixel's points and seeds for the two scrambles.
+    fn next_dimension(&mut self, size: u32) -> (u32, u32, u32) {
+        let hash: u64 = hash(&[self.seed, pixel_key(self.pixel), u64::from(self.dimension)]);
+        self.dimension += size;
+        let index: u32 = permutation_element(self.index, self.samples_per_pixel, hash as u32);
+        (index, (hash >> 32) as u32, mix_bits(hash) as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (index, seed, _) = self.next_dimension(1);
+        to_unit(fast_owen_scramble(sobol(index, 0), seed))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (index, seed_x, seed_y) = self.next_dimension(2);
+        (
+            to_unit(fast_owen_scramble(sobol(index, 0), seed_x)),
+            to_unit(fast_owen_scramble(sobol(index, 1), seed_y)),
+        )
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+/// For previews at low sample counts: each pixel takes the points of a rank-1
+/// lattice, shifted toroidally by values from a blue-noise tile. Neighbouring
+/// pixels then receive very different shifts, which pushes the error towards
+/// high frequencies where it is far less visible than white noise.
+#[derive(Clone)]
+pub struct BlueNoiseSampler {
+    seed: u64,
+    samples_per_pixel: u32,
+    generator: u32,
+    pixel: (i32, i32),
+    index: u32,
+    dimension: u32,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(samples_per_pixel: u32) -> Self {
+        let samples_per_pixel: u32 = samples_per_pixel.max(1);
+        BlueNoiseSampler {
+            seed: 0,
+            samples_per_pixel,
+            generator: lattice_generator(samples_per_pixel),
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Value of the blue-noise tile at the current pixel, with the tile offset
+    /// differently for every dimension.
+    fn shift(&self, dimension: u32) -> f64 {
+        let offset: u64 = hash(&[self.seed, u64::from(dimension)]);
+        let x: usize = (self.pixel.0 as i64 + (offset & 0xffff) as i64)
+            .rem_euclid(BLUE_NOISE_SIZE as i64) as usize;
+        let y: usize = (self.pixel.1 as i64 + (offset >> 16 & 0xffff) as i64)
+            .rem_euclid(BLUE_NOISE_SIZE as i64) as usize;
+        blue_noise_tile()[y * BLUE_NOISE_SIZE + x]
+    }
+
+    /// Lattice point for the current sample; the order of the points is
+    /// shuffled per dimension so dimensions are not correlated.
+    fn next_dimension(&mut self, size: u32) -> (u32, f64, f64) {
+        let dimension: u32 = self.dimension;
+        self.dimension += size;
+        let index: u32 = permutation_element(
+            self.index,
+            self.samples_per_pixel,
+            hash(&[self.seed, u64::from(dimension), 1]) as u32,
+        );
+        (index, self.shift(dimension), self.shift(dimension + 1))
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (i32, i32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (index, shift, _) = self.next_dimension(1);
+        let n: f64 = f64::from(self.samples_per_pixel);
+        ((f64::from(index) / n + shift).fract()).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (index, shift_x, shift_y) = self.next_dimension(2);
+        let n: u64 = u64::from(self.samples_per_pixel);
+        let y: u64 = u64::from(index) * u64::from(self.generator) % n;
+        (
+            ((f64::from(index) / n as f64 + shift_x).fract()).min(ONE_MINUS_EPSILON),
+            ((y as f64 / n as f64 + shift_y).fract()).min(ONE_MINUS_EPSILON),
+        )
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+/// Generator of the rank-1 lattice `(i / n, i g / n)` whose closest pair of
+/// points is furthest apart, searched exhaustively for small `n`.
+fn lattice_generator(n: u32) -> u32 {
+    if n <= 2 {
+        return 1;
+    }
+    if n > 256 {
+        // Golden ratio lattices are good for any size.
+        return ((f64::from(n) * 0.618_033_988_749_895).round() as u32).max(1);
+    }
+
+    let min_distance = |generator: u32| -> f64 {
+        (1..n)
+            .map(|i| {
+                let x: f64 = f64::from(i) / f64::from(n);
+                let y: f64 = f64::from((u64::from(i) * u64::from(generator) % u64::from(n)) as u32)
+                    / f64::from(n);
+                x.min(1. - x).powi(2) + y.min(1. - y).powi(2)
+            })
+            .fold(f64::INFINITY, f64::min)
+    };
+    // Generators `g` and `n - g` mirror each other, so half of them suffice.
+    (1..=n / 2)
+        .map(|generator| (generator, min_distance(generator)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(1, |(generator, _)| generator)
+}
+
+const BLUE_NOISE_SIZE: usize = 64;
+
+/// Tile of `BLUE_NOISE_SIZE²` values in `(0, 1)` with a blue-noise spectrum,
+/// built once by Ulichney's void-and-cluster method.
+fn blue_noise_tile() -> &'static [f64] {
+    static TILE: OnceLock<Vec<f64>> = OnceLock::new();
+    TILE.get_or_init(|| void_and_cluster(BLUE_NOISE_SIZE))
+}
+
+fn void_and_cluster(size: usize) -> Vec<f64> {
+    const SIGMA: f64 = 1.5;
+    const RADIUS: isize = 6;
+
+    let n: usize = size * size;
+    let kernel: Vec<f64> = (-RADIUS..=RADIUS)
+        .flat_map(|dy| (-RADIUS..=RADIUS).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| (-((dx * dx + dy * dy) as f64) / (2. * SIGMA.powi(2))).exp())
+        .collect();
+
+    // Gaussian-filtered density of the set pixels, kept up to date on every change.
+    let splat = |energy: &mut [f64], pixel: usize, sign: f64| {
+        let (x, y) = ((pixel % size) as isize, (pixel / size) as isize);
+        for dy in -RADIUS..=RADIUS {
+            for dx in -RADIUS..=RADIUS {
+                let qx: usize = (x + dx).rem_euclid(size as isize) as usize;
+                let qy: usize = (y + dy).rem_euclid(size as isize) as usize;
+                let weight: f64 = kernel[((dy + RADIUS) * (2 * RADIUS + 1) + dx + RADIUS) as usize];
+                energy[qy * size + qx] += sign * weight;
+            }
+        }
+    };
+    let tightest_cluster = |pattern: &[bool], energy: &[f64]| -> usize {
+        (0..n)
+            .filter(|&p| pattern[p])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f64]| -> usize {
+        (0..n)
+            .filter(|&p| !pattern[p])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // Random initial pattern, relaxed until swapping no longer helps. Ties in
+    // energy could make swaps cycle, so relaxation gives up after `n` swaps.
+    let mut rng = SmallRng::seed_from_u64(0x5eed);
+    let mut pattern: Vec<bool> = vec![false; n];
+    let mut energy: Vec<f64> = vec![0.; n];
+    let initial: usize = n / 10;
+    while pattern.iter().filter(|&&set| set).count() < initial {
+        let pixel: usize = rng.random_range(0..n);
+        if !pattern[pixel] {
+            pattern[pixel] = true;
+            splat(&mut energy, pixel, 1.);
+        }
+    }
+    for _ in 0..n {
+        let cluster: usize = tightest_cluster(&pattern, &energy);
+        pattern[cluster] = false;
+        splat(&mut energy, cluster, -1.);
+        let void: usize = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank: Vec<usize> = vec![0; n];
+
+    // Rank the initial pixels by removing them from the tightest clusters.
+    let (mut removed, mut removed_energy) = (pattern.clone(), energy.clone());
+    for r in (0..initial).rev() {
+        let cluster: usize = tightest_cluster(&removed, &removed_energy);
+        removed[cluster] = false;
+        splat(&mut removed_energy, cluster, -1.);
+        rank[cluster] = r;
+    }
+
+    // Then fill the largest voids until every pixel is ranked.
+    for r in initial..n {
+        let void: usize = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.);
+        rank[void] = r;
+    }
+
+    rank.into_iter()
+        .map(|r| (r as f64 + 0.5) / n as f64)
+        .collect()
+}
+
+/// Finalizer of a 64-bit hash, spreading every input bit over the output.
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e37_79b9_7f4a_7c15, |hash, value| {
+        mix_bits(hash ^ value).wrapping_add(0x9e37_79b9_7f4a_7c15)
+    })
+}
+
+/// Random stream of one sample, independent of the streams of other samples.
+fn sample_stream(seed: u64, pixel: (i32, i32), index: u32) -> SmallRng {
+    SmallRng::seed_from_u64(hash(&[seed, pixel_key(pixel), u64::from(index)]))
+}
+
+fn pixel_key(pixel: (i32, i32)) -> u64 {
+    (u64::from(pixel.0 as u32) << 32) | u64::from(pixel.1 as u32)
+}
+
+/// Element `index` of a random permutation of `0..length` chosen by `seed`,
+/// without storing the permutation (Kensler, "Correlated Multi-Jittered
+/// Sampling").
+pub fn permutation_element(index: u32, length: u32, seed: u32) -> u32 {
+    if length <= 1 {
+        return 0;
+    }
+    let mut w: u32 = length - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    let mut i: u32 = index;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < length {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % length
+}
+
+/// Radical inverse of `index` in `base`, with every digit permuted depending
+/// on the digits before it.
+fn owen_scrambled_radical_inverse(base: u64, mut index: u64, seed: u32) -> f64 {
+    let inverse_base: f64 = 1. / base as f64;
+    let mut inverse_base_power: f64 = 1.;
+    let mut reversed_digits: u64 = 0;
+    // Digits beyond double precision make no difference to the result.
+    while inverse_base_power > 1e-16 {
+        let next: u64 = index / base;
+        let digit: u64 = index - next * base;
+        let digit_seed: u32 = mix_bits(u64::from(seed) ^ reversed_digits) as u32;
+        let digit: u64 = u64::from(permutation_element(digit as u32, base as u32, digit_seed));
+        reversed_digits = reversed_digits * base + digit;
+        inverse_base_power *= inverse_base;
+        index = next;
+    }
+    (inverse_base_power * reversed_digits as f64).min(ONE_MINUS_EPSILON)
+}
+
+/// Generator matrix of the second Sobol dimension, from the primitive
+/// polynomial `x + 1`.
+const SOBOL_MATRIX_1: [u32; 32] = {
+    let mut matrix: [u32; 32] = [0; 32];
+    let mut m: u32 = 1;
+    let mut i: usize = 0;
+    while i < 32 {
+        matrix[i] = m << (31 - i);
+        if i < 31 {
+            m ^= m << 1;
+        }
+        i += 1;
+    }
+    matrix
+};
+
+/// First two dimensions of the Sobol sequence, as 32-bit fixed point.
+fn sobol(index: u32, dimension: usize) -> u32 {
+    match dimension {
+        0 => index.reverse_bits(),
+        _ => (0..32)
+            .filter(|bit| index >> bit & 1 == 1)
+            .fold(0, |value, bit| value ^ SOBOL_MATRIX_1[bit]),
+    }
+}
+
+/// Owen scrambling of a 32-bit fixed point value through a hash that only lets
+/// bits influence less significant ones (Laine and Karras).
+fn fast_owen_scramble(value: u32, seed: u32) -> u32 {
+    let mut v: u32 = value.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20_adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x0552_6c56);
+    v ^= v.wrapping_mul(0x53a2_2864);
+    v.reverse_bits()
+}
+
+fn to_unit(value: u32) -> f64 {
+    (f64::from(value) / (1_u64 << 32) as f64).min(ONE_MINUS_EPSILON)
+}
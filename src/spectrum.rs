@@ -0,0 +1,178 @@
+use std::sync::OnceLock;
+
+use crate::color::Color;
+
+extern crate nalgebra as na;
+
+/// Visible range sampled in spectral mode, in nanometres.
+pub const LAMBDA_MIN: f64 = 380.;
+pub const LAMBDA_MAX: f64 = 720.;
+
+/// Helium d-line, used for dispersive media when a ray carries no wavelength.
+pub const LAMBDA_D_LINE: f64 = 587.6;
+
+/// Maps a uniform sample in `[0, 1)` to a wavelength.
+pub fn sample_wavelength(u: f64) -> f64 {
+    LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+pub fn wavelength_pdf() -> f64 {
+    1. / (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+// Smits, "An RGB-to-Spectrum Conversion for Reflectances", sampled at ten
+// evenly spaced wavelengths over the visible range.
+const SMITS_WHITE: [f64; 10] = [1., 1., 0.9999, 0.9993, 0.9992, 0.9998, 1., 1., 1., 1.];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0., 0., 0.,
+];
+const SMITS_MAGENTA: [f64; 10] = [1., 1., 0.9685, 0.2229, 0., 0.0458, 0.8369, 1., 1., 0.9959];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0., 0.1088, 0.6651, 1., 1., 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0., 0., 0., 0., 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [0., 0., 0.0273, 0.7937, 1., 0.9418, 0.1719, 0., 0., 0.0025];
+const SMITS_BLUE: [f64; 10] = [
+    1., 1., 0.8916, 0.3323, 0., 0., 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+fn smits(table: &[f64; 10], wavelength: f64) -> f64 {
+    let x: f64 = ((wavelength - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN) * 9.).clamp(0., 9.);
+    let i: usize = (x.floor() as usize).min(8);
+    let t: f64 = x - i as f64;
+    (1. - t) * table[i] + t * table[i + 1]
+}
+
+/// Value at `wavelength` of a smooth spectrum whose colour is `color`.
+pub fn rgb_to_spectral(color: &Color, wavelength: f64) -> f64 {
+    let (r, g, b) = (color.x, color.y, color.z);
+    let basis = |table: &[f64; 10]| smits(table, wavelength);
+
+    if r <= g && r <= b {
+        r * basis(&SMITS_WHITE)
+            + if g <= b {
+                (g - r) * basis(&SMITS_CYAN) + (b - g) * basis(&SMITS_BLUE)
+            } else {
+                (b - r) * basis(&SMITS_CYAN) + (g - b) * basis(&SMITS_GREEN)
+            }
+    } else if g <= r && g <= b {
+        g * basis(&SMITS_WHITE)
+            + if r <= b {
+                (r - g) * basis(&SMITS_MAGENTA) + (b - r) * basis(&SMITS_BLUE)
+            } else {
+                (b - g) * basis(&SMITS_MAGENTA) + (r - b) * basis(&SMITS_RED)
+            }
+    } else {
+        b * basis(&SMITS_WHITE)
+            + if r <= g {
+                (r - b) * basis(&SMITS_YELLOW) + (g - r) * basis(&SMITS_GREEN)
+            } else {
+                (g - b) * basis(&SMITS_YELLOW) + (r - g) * basis(&SMITS_RED)
+            }
+    }
+}
+
+/// Leaves `color` untouched for RGB rays; for spectral rays returns its value at
+/// the ray's wavelength, replicated across all channels.
+pub fn project(color: Color, wavelength: Option<f64>) -> Color {
+    match wavelength {
+        Some(wavelength) => Color::repeat(rgb_to_spectral(&color, wavelength)),
+        None => color,
+    }
+}
+
+/// CIE 1931 colour matching functions, using the multi-lobe Gaussian fit of
+/// Wyman, Sloan and Shirley (2013).
+pub fn xyz_matching(wavelength: f64) -> na::Vector3<f64> {
+    let g = |mu: f64, sigma_1: f64, sigma_2: f64| -> f64 {
+        let sigma: f64 = if wavelength < mu { sigma_1 } else { sigma_2 };
+        (-0.5 * ((wavelength - mu) / sigma).powi(2)).exp()
+    };
+
+    na::Vector3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+/// Linear sRGB from CIE XYZ (D65).
+pub fn xyz_to_rgb(xyz: &na::Vector3<f64>) -> Color {
+    let m = na::Matrix3::new(
+        3.2404542, -1.5371385, -0.4985314, //
+        -0.9692660, 1.8760108, 0.0415560, //
+        0.0556434, -0.2040259, 1.0572252,
+    );
+    m * xyz
+}
+
+/// Integrals of the matching functions over the sampled range, and the colour
+/// of a constant unit spectrum used to white balance the result.
+fn normalization() -> &'static (f64, Color) {
+    static NORMALIZATION: OnceLock<(f64, Color)> = OnceLock::new();
+    NORMALIZATION.get_or_init(|| {
+        let steps: usize = 3400;
+        let step: f64 = (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+        let integral: na::Vector3<f64> = (0..steps)
+            .map(|i| xyz_matching(LAMBDA_MIN + (i as f64 + 0.5) * step) * step)
+            .sum();
+        (integral.y, xyz_to_rgb(&(integral / integral.y)))
+    })
+}
+
+/// Converts one spectral radiance sample, drawn with density `pdf`, to a
+/// white-balanced linear RGB estimate.
+pub fn spectral_to_rgb(radiance: f64, wavelength: f64, pdf: f64) -> Color {
+    let (y_integral, white) = normalization();
+    let xyz: na::Vector3<f64> = xyz_matching(wavelength) * radiance / (pdf * y_integral);
+    xyz_to_rgb(&xyz).component_div(white)
+}
+
+/// Wavelength-dependent refractive index.
+#[derive(Debug, Clone, Copy)]
+pub enum RefractiveIndex {
+    Constant(f64),
+    /// `n = a + b / λ²`, with `λ` in micrometres.
+    Cauchy {
+        a: f64,
+        b: f64,
+    },
+    /// `n² = 1 + Σ b λ² / (λ² - c)`, with `λ` in micrometres.
+    Sellmeier {
+        b: [f64; 3],
+        c: [f64; 3],
+    },
+}
+
+impl RefractiveIndex {
+    /// Schott N-BK7 crown glass.
+    pub const BK7: Self = RefractiveIndex::Sellmeier {
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    };
+
+    /// Fused silica.
+    pub const FUSED_SILICA: Self = RefractiveIndex::Sellmeier {
+        b: [0.6961663, 0.4079426, 0.8974794],
+        c: [0.0046791, 0.0135121, 97.934003],
+    };
+
+    /// Evaluates the index at `wavelength` in nanometres, or at the d-line
+    /// for rays without a wavelength.
+    pub fn at(&self, wavelength: Option<f64>) -> f64 {
+        let micrometres: f64 = wavelength.unwrap_or(LAMBDA_D_LINE) / 1000.;
+        let lambda_2: f64 = micrometres.powi(2);
+        match self {
+            RefractiveIndex::Constant(n) => *n,
+            RefractiveIndex::Cauchy { a, b } => a + b / lambda_2,
+            RefractiveIndex::Sellmeier { b, c } => (1.
+                + b.iter()
+                    .zip(c)
+                    .map(|(b, c)| b * lambda_2 / (lambda_2 - c))
+                    .sum::<f64>())
+            .sqrt(),
+        }
+    }
+}
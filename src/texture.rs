@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use crate::{
+    color::Color,
+    hittable::HitRecord,
+    ray::Ray,
+    vector::{Onb, Point, R3, Vector},
+};
+
+pub trait Texture: Sync + Send {
+    fn value(&self, u: f64, v: f64, point: &Point) -> Color;
+
+    /// Averages the texture over a square footprint of `width` in uv space.
+    fn filtered_value(&self, u: f64, v: f64, point: &Point, _width: f64) -> Color {
+        self.value(u, v, point)
+    }
+
+    /// Looks up the texture at a hit, filtered by the footprint of the ray when
+    /// it carries differentials.
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord) -> Color {
+        let (u, v) = hit_record.uv();
+        match hit_record.differentials(ray) {
+            Some(differentials) => {
+                self.filtered_value(u, v, &hit_record.point(), differentials.uv_width())
+            }
+            None => self.value(u, v, &hit_record.point()),
+        }
+    }
+}
+
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        SolidColor { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _point: &Point) -> Color {
+        self.albedo
+    }
+}
+
+/// Image texture with a box-filtered mip pyramid for footprint-aware lookups.
+pub struct ImageTexture {
+    levels: Vec<MipLevel>,
+}
+
+impl ImageTexture {
+    /// `pixels` are stored row by row, starting from the top-left corner.
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0, "image texture must not be empty");
+        assert_eq!(pixels.len(), width * height, "pixel count mismatch");
+
+        let mut levels: Vec<MipLevel> = vec![MipLevel {
+            width,
+            height,
+            pixels,
+        }];
+        while let Some(level) = levels.last().filter(|l| l.width > 1 || l.height > 1) {
+            levels.push(level.downsample());
+        }
+
+        ImageTexture { levels }
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _point: &Point) -> Color {
+        self.levels[0].bilinear(u, v)
+    }
+
+    fn filtered_value(&self, u: f64, v: f64, _point: &Point, width: f64) -> Color {
+        // Trilinear filtering between the two levels closest to the footprint.
+        let texels: f64 = width * self.width().max(self.height()) as f64;
+        let level: f64 = texels
+            .max(1e-8)
+            .log2()
+            .clamp(0., (self.levels.len() - 1) as f64);
+        let lower: usize = level.floor() as usize;
+        let upper: usize = (lower + 1).min(self.levels.len() - 1);
+        let weight: f64 = level - lower as f64;
+
+        (1. - weight) * self.levels[lower].bilinear(u, v)
+            + weight * self.levels[upper].bilinear(u, v)
+    }
+}
+
+struct MipLevel {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl MipLevel {
+    fn texel(&self, x: isize, y: isize) -> Color {
+        let x: usize = x.rem_euclid(self.width as isize) as usize;
+        let y: usize = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    /// Bilinear lookup, repeating horizontally and clamping vertically.
+    fn bilinear(&self, u: f64, v: f64) -> Color {
+        let x: f64 = u * self.width as f64 - 0.5;
+        let y: f64 = (1. - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        (1. - dx) * (1. - dy) * self.texel(x0, y0)
+            + dx * (1. - dy) * self.texel(x0 + 1, y0)
+            + (1. - dx) * dy * self.texel(x0, y0 + 1)
+            + dx * dy * self.texel(x0 + 1, y0 + 1)
+    }
+
+    fn downsample(&self) -> MipLevel {
+        let width: usize = self.width.div_ceil(2);
+        let height: usize = self.height.div_ceil(2);
+        let pixels: Vec<Color> = (0..height as isize)
+            .flat_map(|y| (0..width as isize).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (x1, y1) = ((2 * x + 1).min(self.width as isize - 1), 2 * y + 1);
+                (self.texel(2 * x, 2 * y)
+                    + self.texel(x1, 2 * y)
+                    + self.texel(2 * x, y1)
+                    + self.texel(x1, y1))
+                    / 4.
+            })
+            .collect();
+
+        MipLevel {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// Replaces the shading normal of a hit without touching its geometric normal.
+pub trait NormalPerturbation: Sync + Send {
+    fn perturb(&self, hit_record: &HitRecord) -> Vector;
+}
+
+pub struct BumpMap {
+    height: Arc<dyn Texture>,
+    scale: f64,
+}
+
+impl BumpMap {
+    pub fn new(height: Arc<dyn Texture>, scale: f64) -> Self {
+        BumpMap { height, scale }
+    }
+
+    fn displacement(&self, u: f64, v: f64, point: &Point) -> f64 {
+        self.scale * self.height.value(u, v, point).mean()
+    }
+}
+
+impl NormalPerturbation for BumpMap {
+    fn perturb(&self, hit_record: &HitRecord) -> Vector {
+        const DELTA: f64 = 5e-4;
+
+        let (u, v) = hit_record.uv();
+        let point: Point = hit_record.point();
+        let normal: Vector = hit_record.outward_normal();
+
+        let displacement: f64 = self.displacement(u, v, &point);
+        let du: f64 = (self.displacement(u + DELTA, v, &point) - displacement) / DELTA;
+        let dv: f64 = (self.displacement(u, v + DELTA, &point) - displacement) / DELTA;
+
+        let dpdu: Vector = hit_record.dpdu() + du * normal;
+        let dpdv: Vector = hit_record.dpdv() + dv * normal;
+
+        let bumped: Vector = dpdu.cross(&dpdv);
+        if bumped.norm_squared() < 1e-16 {
+            return hit_record.shading_normal();
+        }
+
+        hit_record.face_forward(bumped.normalize())
+    }
+}
+
+/// Tangent-space normal map, with the blue channel along the surface normal.
+pub struct NormalMap {
+    map: Arc<dyn Texture>,
+}
+
+impl NormalMap {
+    pub fn new(map: Arc<dyn Texture>) -> Self {
+        NormalMap { map }
+    }
+}
+
+impl NormalPerturbation for NormalMap {
+    fn perturb(&self, hit_record: &HitRecord) -> Vector {
+        let (u, v) = hit_record.uv();
+        let local: Vector = 2. * self.map.value(u, v, &hit_record.point()) - Vector::repeat(1.);
+        if local.near_zero() {
+            return hit_record.shading_normal();
+        }
+
+        let frame = Onb::with_tangent(&hit_record.outward_normal(), &hit_record.dpdu());
+        hit_record.face_forward(frame.to_world(&local).normalize())
+    }
+}
@@ -41,3 +41,57 @@ impl R3 for Vector {
         out_prep + out_parallel
     }
 }
+
+/// Orthonormal basis whose `w` axis is aligned with a given normal.
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    u: Vector,
+    v: Vector,
+    w: Vector,
+}
+
+impl Onb {
+    pub fn new(normal: &Vector) -> Self {
+        // Duff et al., "Building an Orthonormal Basis, Revisited".
+        let w: Vector = normal.normalize();
+        let sign: f64 = 1_f64.copysign(w.z());
+        let a: f64 = -1. / (sign + w.z());
+        let b: f64 = w.x() * w.y() * a;
+        let u: Vector = Vector::new(1. + sign * w.x().powi(2) * a, sign * b, -sign * w.x());
+        let v: Vector = Vector::new(b, sign + w.y().powi(2) * a, -w.y());
+
+        Onb { u, v, w }
+    }
+
+    pub fn with_tangent(normal: &Vector, tangent: &Vector) -> Self {
+        let w: Vector = normal.normalize();
+        let t: Vector = tangent - tangent.dot(&w) * w;
+        if t.near_zero() {
+            return Onb::new(normal);
+        }
+        let u: Vector = t.normalize();
+        let v: Vector = w.cross(&u);
+
+        Onb { u, v, w }
+    }
+
+    pub fn u(&self) -> Vector {
+        self.u
+    }
+
+    pub fn v(&self) -> Vector {
+        self.v
+    }
+
+    pub fn w(&self) -> Vector {
+        self.w
+    }
+
+    pub fn to_world(&self, local: &Vector) -> Vector {
+        local.x() * self.u + local.y() * self.v + local.z() * self.w
+    }
+
+    pub fn to_local(&self, world: &Vector) -> Vector {
+        Vector::new(world.dot(&self.u), world.dot(&self.v), world.dot(&self.w))
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    INFINITY,
+    color::Color,
+    hittable::{HittableList, Sphere},
+    integrator::{Integrator, IntegratorConfig, PathIntegrator, RecursiveIntegrator, sky_box},
+    interval::Interval,
+    material::Dielectric,
+    ray::Ray,
+    sampler::IndependentSampler,
+    vector::{Point, Vector},
+};
+
+#[test]
+fn follows_beer_lambert() {
+    // Without a change in refractive index rays cross the sphere undeflected.
+    let absorption: Color = Color::new(0.2, 0.7, 1.5);
+    let world = HittableList::new(vec![Arc::new(Sphere::new(
+        Point::origin(),
+        1.,
+        Arc::new(Dielectric::new(1.).with_absorption(absorption)),
+    ))]);
+    let integrators: [Box<dyn Integrator>; 2] = [
+        Box::new(RecursiveIntegrator::new(IntegratorConfig::new(8))),
+        Box::new(PathIntegrator::new(IntegratorConfig::new(8))),
+    ];
+
+    for offset in [0., 0.6, 0.9] {
+        let ray: Ray = Ray::new(Point::new(offset, 0., 5.), Vector::new(0., 0., -1.));
+        let distance: f64 = 2. * (1. - offset * offset).sqrt();
+        let expected: Color = (-absorption * distance)
+            .map(f64::exp)
+            .component_mul(&sky_box(&ray));
+
+        for integrator in &integrators {
+            let mut sampler = IndependentSampler::new(1);
+            let radiance: Color =
+                integrator.radiance(&ray, &world, Interval::new(0.001, INFINITY), &mut sampler);
+            assert!(
+                (radiance - expected).norm() < 1e-9,
+                "{radiance:?} {expected:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    INFINITY,
+    camera::Camera,
+    color::Color,
+    hittable::{HittableList, Sphere},
+    integrator::{IntegratorConfig, PathIntegrator},
+    interval::Interval,
+    material::Lambertian,
+    vector::{Point, Vector},
+};
+
+#[test]
+fn sky_pixels_stop_early() {
+    let camera: Camera = Camera::new(
+        16,
+        1.,
+        60.,
+        Point::new(0., 0., 4.),
+        Point::origin(),
+        Vector::new(0., 1., 0.),
+        0.,
+        4.,
+        64,
+    )
+    .with_adaptive_sampling(8, 0.02);
+    let world = HittableList::new(vec![Arc::new(Sphere::new(
+        Point::origin(),
+        0.8,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    ))]);
+    let integrator = PathIntegrator::new(IntegratorConfig::new(10));
+
+    let (_, counts) =
+        camera.render_image_with_counts(&world, &integrator, Interval::new(0.001, INFINITY));
+
+    // The corners only see sky, the centre sees the noisy diffuse sphere.
+    assert_eq!(counts[0], 8);
+    assert_eq!(counts[counts.len() - 1], 8);
+    assert!(counts[8 * 16 + 8] > 8);
+    assert!(counts.iter().all(|&count| (8..=64).contains(&count)));
+}
+
+#[test]
+fn render_passes_reuse_the_sample_counts() {
+    let camera: Camera = Camera::new(
+        16,
+        1.,
+        60.,
+        Point::new(0., 0., 4.),
+        Point::origin(),
+        Vector::new(0., 1., 0.),
+        0.,
+        4.,
+        64,
+    )
+    .with_adaptive_sampling(8, 0.02);
+    let world = HittableList::new(vec![Arc::new(Sphere::new(
+        Point::origin(),
+        0.8,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    ))]);
+    let integrator = PathIntegrator::new(IntegratorConfig::new(10));
+    let time: Interval = Interval::new(0.001, INFINITY);
+
+    // The same integrator as a pass retraces exactly the samples of the image.
+    let (image, counts) = camera.render_image_with_counts(&world, &integrator, time);
+    assert_eq!(
+        camera.render_pass(&world, &integrator, time, &counts),
+        image
+    );
+
+    // Other counts give another image.
+    let full: Vec<u32> = vec![64; counts.len()];
+    assert_ne!(camera.render_pass(&world, &integrator, time, &full), image);
+}
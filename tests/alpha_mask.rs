@@ -0,0 +1,90 @@
+use nalgebra::Complex;
+use std::sync::Arc;
+
+use ray_tracer::{
+    INFINITY,
+    color::Color,
+    hittable::{Hittable, HittableList, Sphere},
+    interval::Interval,
+    material::{AlphaMask, AlphaMode, Coated, Lambertian, Material, ThinFilm},
+    ray::Ray,
+    sampler::{IndependentSampler, Sampler},
+    texture::SolidColor,
+    vector::{Point, Vector},
+};
+
+fn masked(alpha: f64, mode: AlphaMode) -> Arc<dyn Material> {
+    Arc::new(AlphaMask::new(
+        Arc::new(Lambertian::new(Color::new(0.8, 0.2, 0.2))),
+        Arc::new(SolidColor::new(Color::repeat(alpha))),
+        mode,
+    ))
+}
+
+#[test]
+fn transparent_mask_is_ignored_by_hit() {
+    let backdrop: Arc<dyn Hittable> = Arc::new(Sphere::new(
+        Point::new(0., 0., -10.),
+        1.,
+        Arc::new(Lambertian::new(Color::repeat(0.5))),
+    ));
+
+    for mode in [AlphaMode::Threshold(0.5), AlphaMode::Stochastic] {
+        let cut_out: Arc<dyn Material> = masked(0., mode);
+        // Wrapping materials keep the cutout of their base.
+        let materials: [Arc<dyn Material>; 3] = [
+            cut_out.clone(),
+            Arc::new(Coated::new(cut_out.clone(), 1.5)),
+            Arc::new(ThinFilm::new(cut_out, 300., 1.33, Complex::new(1.5, 0.))),
+        ];
+
+        for material in materials {
+            let world = HittableList::new(vec![
+                Arc::new(Sphere::new(Point::new(0., 0., -2.), 0.5, material)),
+                backdrop.clone(),
+            ]);
+            let mut sampler = IndependentSampler::new(1);
+            for _ in 0..100 {
+                let (u, v) = sampler.get_2d();
+                let ray: Ray = Ray::new(
+                    Point::new(0.4 * (u - 0.5), 0.4 * (v - 0.5), 0.),
+                    -Vector::z(),
+                );
+                let hit_record = world.hit(&ray, Interval::new(0.001, INFINITY)).unwrap();
+                assert!(hit_record.t() > 8.);
+            }
+        }
+    }
+}
+
+#[test]
+fn half_alpha_passes_half_the_rays() {
+    const RAYS: usize = 20_000;
+    let sphere = Sphere::new(
+        Point::new(0., 0., -2.),
+        0.5,
+        masked(0.5, AlphaMode::Stochastic),
+    );
+    let mut sampler = IndependentSampler::new(1);
+
+    let (mut front, mut missed): (usize, usize) = (0, 0);
+    for _ in 0..RAYS {
+        let (u, v) = sampler.get_2d();
+        let ray: Ray = Ray::new(
+            Point::new(0.2 * (u - 0.5), 0.2 * (v - 0.5), 0.),
+            -Vector::z(),
+        );
+        match sphere.hit(&ray, Interval::new(0.001, INFINITY)) {
+            Some(hit_record) if hit_record.front_face() => front += 1,
+            Some(_) => {}
+            None => missed += 1,
+        }
+    }
+
+    // Rays passing the front may still stop at the back of the sphere.
+    assert!((front as f64 / RAYS as f64 - 0.5).abs() < 0.02, "{front}");
+    assert!(
+        (missed as f64 / RAYS as f64 - 0.25).abs() < 0.02,
+        "{missed}"
+    );
+}
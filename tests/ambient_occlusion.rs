@@ -0,0 +1,99 @@
+use std::{path::PathBuf, sync::Arc};
+
+use ray_tracer::{
+    INFINITY,
+    camera::Camera,
+    color::{Color, Color3},
+    hittable::{HittableList, Sphere},
+    integrator::{AmbientOcclusionIntegrator, Integrator, IntegratorConfig, PathIntegrator},
+    interval::Interval,
+    material::Lambertian,
+    ray::Ray,
+    sampler::{IndependentSampler, Sampler},
+    vector::{Point, Vector},
+};
+
+fn occlusion(world: &HittableList, target: Point) -> f64 {
+    let integrator = AmbientOcclusionIntegrator::new(1., 256);
+    let mut sampler = IndependentSampler::new(1);
+    sampler.start_pixel_sample((0, 0), 0);
+    let ray: Ray = Ray::new(target + Vector::new(0., 3., 3.), Vector::new(0., -3., -3.));
+    integrator
+        .radiance(&ray, world, Interval::new(0.001, INFINITY), &mut sampler)
+        .x
+}
+
+#[test]
+fn darkens_near_contact() {
+    let ground = Arc::new(Lambertian::new(Color::repeat(0.5)));
+    let mut world = HittableList::new(vec![Arc::new(Sphere::new(
+        Point::new(0., -1000., 0.),
+        1000.,
+        ground.clone(),
+    ))]);
+    assert_eq!(occlusion(&world, Point::new(0.6, 0., 0.)), 1.);
+
+    world.push(Arc::new(Sphere::new(Point::new(0., 1., 0.), 1., ground)));
+    let near: f64 = occlusion(&world, Point::new(0.6, 0., 0.));
+    let far: f64 = occlusion(&world, Point::new(1.5, 0., 0.));
+    assert!(near < far && far < 1.);
+    assert_eq!(occlusion(&world, Point::new(3., 0., 0.)), 1.);
+
+    let miss: Ray = Ray::new(Point::new(0., 1., 5.), Vector::new(0., 1., 0.));
+    let mut sampler = IndependentSampler::new(1);
+    let sky: Color = AmbientOcclusionIntegrator::new(1., 16).radiance(
+        &miss,
+        &world,
+        Interval::new(0.001, INFINITY),
+        &mut sampler,
+    );
+    assert_eq!(sky, Color::zeros());
+}
+
+#[test]
+fn renders_as_a_pass_alongside_beauty() {
+    let ground = Arc::new(Lambertian::new(Color::repeat(0.5)));
+    let world = HittableList::new(vec![
+        Arc::new(Sphere::new(
+            Point::new(0., -1000., 0.),
+            1000.,
+            ground.clone(),
+        )),
+        Arc::new(Sphere::new(Point::new(0., 1., 0.), 1., ground)),
+    ]);
+    let time: Interval = Interval::new(0.001, INFINITY);
+    let ambient_occlusion: Arc<AmbientOcclusionIntegrator> =
+        Arc::new(AmbientOcclusionIntegrator::new(1., 4));
+    let path: PathBuf = std::env::temp_dir().join("ray_tracer_ambient_occlusion_pass.ppm");
+
+    let camera = || {
+        Camera::new(
+            6,
+            1.5,
+            60.,
+            Point::new(0., 2., 5.),
+            Point::new(0., 0.5, 0.),
+            Vector::new(0., 1., 0.),
+            0.,
+            1.,
+            16,
+        )
+        .with_adaptive_sampling(4, 0.05)
+    };
+    let beauty = PathIntegrator::new(IntegratorConfig::new(4));
+    camera()
+        .with_render_pass(&path, ambient_occlusion.clone())
+        .render(HittableList::new(world.clone()), &beauty, time);
+
+    // The pass stops where the beauty image did, not where it would converge.
+    let (_, counts) = camera().render_image_with_counts(&world, &beauty, time);
+    assert!(counts.iter().any(|&count| count < 16));
+    let expected: String = camera()
+        .render_pass(&world, ambient_occlusion.as_ref(), time, &counts)
+        .iter()
+        .fold(String::from("P3\n6 4\n255\n"), |ppm, pixel| {
+            ppm + &pixel.write() + "\n"
+        });
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+    std::fs::remove_file(&path).unwrap();
+}
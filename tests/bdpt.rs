@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    INFINITY,
+    bdpt::BdptIntegrator,
+    camera::Camera,
+    color::Color,
+    hittable::{Hittable, HittableList, Sphere},
+    integrator::{Integrator, IntegratorConfig, PathIntegrator},
+    interval::Interval,
+    material::{DiffuseLight, Lambertian},
+    vector::{Point, Vector},
+};
+
+/// Mean pixel of a diffuse room lit by a light behind the camera.
+fn mean_pixel(integrator: &dyn Integrator, samples_per_pixel: i32) -> Color {
+    let light: Arc<dyn Hittable> = Arc::new(Sphere::new(
+        Point::new(0.2, 3.8, 5.5),
+        0.5,
+        Arc::new(DiffuseLight::new(Color::repeat(20.))),
+    ));
+    let world = HittableList::new(vec![
+        Arc::new(Sphere::new(
+            Point::new(0., -1000., 0.),
+            1000.,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )),
+        Arc::new(Sphere::new(
+            Point::new(-1.4, 1.3, -1.),
+            1.3,
+            Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1))),
+        )),
+        Arc::new(Sphere::new(
+            Point::origin(),
+            20.,
+            Arc::new(Lambertian::new(Color::repeat(0.3))),
+        )),
+        light,
+    ]);
+
+    let camera: Camera = Camera::new(
+        8,
+        2.,
+        80.,
+        Point::new(0.2, 2.6, 4.),
+        Point::new(0.6, 1.3, -0.6),
+        Vector::new(0., 1., 0.),
+        0.,
+        1.,
+        samples_per_pixel,
+    );
+    let image: Vec<Color> = camera.render_image(&world, integrator, Interval::new(0.001, INFINITY));
+    image.iter().sum::<Color>() / image.len() as f64
+}
+
+fn lights() -> Arc<HittableList> {
+    Arc::new(HittableList::new(vec![Arc::new(Sphere::new(
+        Point::new(0.2, 3.8, 5.5),
+        0.5,
+        Arc::new(DiffuseLight::new(Color::repeat(20.))),
+    ))]))
+}
+
+#[test]
+fn agrees_with_path_tracing() {
+    let config: IntegratorConfig = IntegratorConfig::new(4).with_lights(lights());
+    let path: Color = mean_pixel(
+        &PathIntegrator::new(config.clone()).with_roulette_depth(4),
+        2048,
+    );
+    let bdpt: Color = mean_pixel(&BdptIntegrator::new(config), 512);
+
+    assert!(
+        ((bdpt - path).norm() / path.norm()) < 0.03,
+        "{bdpt:?} differs from {path:?}"
+    );
+}
@@ -0,0 +1,42 @@
+use ray_tracer::sampler::{BlueNoiseSampler, Sampler};
+
+const TILE: i32 = 64;
+
+/// Shortest distance between pixels of the tile whose value is below
+/// `threshold`, wrapping around its edges.
+fn min_distance(tile: &[f64], threshold: f64) -> f64 {
+    let pixels: Vec<(i32, i32)> = (0..TILE * TILE)
+        .filter(|&i| tile[i as usize] < threshold)
+        .map(|i| (i % TILE, i / TILE))
+        .collect();
+    let wrapped = |d: i32| -> i32 { d.rem_euclid(TILE).min(TILE - d.rem_euclid(TILE)) };
+
+    pixels
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| pixels[i + 1..].iter().map(move |b| (a, b)))
+        .map(|(a, b)| f64::from(wrapped(a.0 - b.0).pow(2) + wrapped(a.1 - b.1).pow(2)).sqrt())
+        .fold(f64::INFINITY, f64::min)
+}
+
+#[test]
+fn tile_spreads_low_values_apart() {
+    // With one sample per pixel, the first dimension is the tile value itself.
+    let mut sampler = BlueNoiseSampler::new(1);
+    let tile: Vec<f64> = (0..TILE * TILE)
+        .map(|i| {
+            sampler.start_pixel_sample((i % TILE, i / TILE), 0);
+            sampler.get_1d()
+        })
+        .collect();
+
+    // White noise would put some of these pixels right next to each other.
+    assert!(min_distance(&tile, 0.05) >= 3.);
+    assert!(min_distance(&tile, 0.1) >= 2.);
+
+    let mut sorted: Vec<f64> = tile.clone();
+    sorted.sort_by(f64::total_cmp);
+    for (rank, value) in sorted.into_iter().enumerate() {
+        assert!((value - (rank as f64 + 0.5) / f64::from(TILE * TILE)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    PI,
+    color::Color,
+    distribution::UniformSphere,
+    hittable::HitRecord,
+    material::{Conductor, Material},
+    ray::Ray,
+    sampler::{IndependentSampler, Sampler},
+    vector::{Point, Vector},
+};
+
+const SAMPLES: usize = 50_000;
+
+fn incoming(angle: f64) -> Ray {
+    let direction: Vector = Vector::new(angle.to_radians().sin(), 0., -angle.to_radians().cos());
+    Ray::new(Point::origin() - direction, direction)
+}
+
+fn hit(material: Arc<dyn Material>) -> HitRecord {
+    HitRecord::new(Point::origin(), Vector::z(), material, 1., true)
+        .with_tangents(Vector::x(), Vector::y())
+}
+
+#[test]
+fn normal_incidence_matches_known_f0() {
+    // Linear F0 from Real-Time Rendering, 4th edition, table 9.2.
+    let metals: [(Conductor, Color); 2] = [
+        (Conductor::gold(0.), Color::new(1., 0.782, 0.344)),
+        (Conductor::copper(0.), Color::new(0.955, 0.638, 0.538)),
+    ];
+
+    for (conductor, f0) in metals {
+        let material: Arc<dyn Material> = Arc::new(conductor);
+        let ray: Ray = incoming(0.);
+        let mut sampler = IndependentSampler::new(1);
+        let scattering = material
+            .scatter(&ray, &hit(material.clone()), &mut sampler)
+            .unwrap();
+        assert!((scattering.ray().direction().normalize() - Vector::z()).norm() < 1e-9);
+        let error: Color = scattering.attenuation() - f0;
+        assert!(error.abs().max() < 0.035, "{:?}", scattering.attenuation());
+    }
+}
+
+#[test]
+fn rough_conductor_passes_furnace_test() {
+    // Close to a perfect mirror, so any gain or loss comes from the microfacet lobe.
+    let eta: Color = Color::repeat(1.);
+    let k: Color = Color::repeat(1e3);
+
+    for roughness in [0.2, 0.5, 1.] {
+        let material: Arc<dyn Material> = Arc::new(Conductor::new(eta, k, roughness));
+        let hit_record: HitRecord = hit(material.clone());
+
+        for angle in [0., 45., 75.] {
+            let ray: Ray = incoming(angle);
+            let mut sampler = IndependentSampler::new(1);
+
+            let sampled: f64 = (0..SAMPLES)
+                .filter_map(|_| material.scatter(&ray, &hit_record, &mut sampler))
+                .map(|scattering| {
+                    assert!(scattering.attenuation().max() <= 1. + 1e-9);
+                    scattering.attenuation().x
+                })
+                .sum::<f64>()
+                / SAMPLES as f64;
+
+            // The same albedo from the evaluated lobe over uniform directions.
+            let evaluated: f64 = (0..SAMPLES)
+                .map(|_| UniformSphere.warp(sampler.get_2d()))
+                .filter(|direction| direction.z > 0.)
+                .map(|direction| material.eval(&ray, &hit_record, &direction).x * 4. * PI)
+                .sum::<f64>()
+                / SAMPLES as f64;
+
+            assert!(sampled <= 1., "{roughness} {angle}: {sampled}");
+            assert!(evaluated < 1.02, "{roughness} {angle}: {evaluated}");
+            // Uniform directions rarely find the narrow lobes of smoother surfaces.
+            if roughness >= 0.5 {
+                assert!((sampled - evaluated).abs() < 0.02, "{sampled} {evaluated}");
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    INFINITY,
+    color::Color,
+    hittable::{HittableList, Sphere},
+    integrator::{DebugIntegrator, DebugView, Integrator},
+    interval::Interval,
+    material::Lambertian,
+    ray::Ray,
+    sampler::{IndependentSampler, Sampler},
+    vector::{Point, Vector},
+};
+
+fn view(view: DebugView, world: &HittableList, ray: &Ray) -> Color {
+    let mut sampler = IndependentSampler::new(1);
+    sampler.start_pixel_sample((0, 0), 0);
+    DebugIntegrator::new(view).radiance(ray, world, Interval::new(0.001, INFINITY), &mut sampler)
+}
+
+#[test]
+fn shows_hit_properties() {
+    let material = Arc::new(Lambertian::new(Color::repeat(0.5)));
+    let world = HittableList::new(vec![
+        Arc::new(Sphere::new(Point::new(-2., 0., 0.), 1., material.clone())),
+        Arc::new(Sphere::new(Point::new(2., 0., 0.), 1., material)),
+    ]);
+    let left: Ray = Ray::new(Point::new(-2., 0., 5.), Vector::new(0., 0., -1.));
+    let right: Ray = Ray::new(Point::new(2., 0., 5.), Vector::new(0., 0., -1.));
+    let inside: Ray = Ray::new(Point::new(-2., 0., 0.), Vector::new(0., 0., -1.));
+
+    assert_eq!(
+        view(DebugView::FrontFace, &world, &left),
+        Color::new(0., 1., 0.)
+    );
+    assert_eq!(
+        view(DebugView::FrontFace, &world, &inside),
+        Color::new(1., 0., 0.)
+    );
+    assert_eq!(
+        view(DebugView::GeometricNormal, &world, &left),
+        Color::new(0.5, 0.5, 1.)
+    );
+    assert_eq!(
+        view(DebugView::GeometricNormal, &world, &inside),
+        Color::new(0.5, 0.5, 1.)
+    );
+    assert_eq!(
+        view(DebugView::OutwardNormal, &world, &inside),
+        Color::new(0.5, 0.5, 0.)
+    );
+    assert_eq!(
+        view(DebugView::Depth(8.), &world, &left),
+        Color::repeat(0.5)
+    );
+
+    assert_ne!(
+        view(DebugView::ObjectId, &world, &left),
+        view(DebugView::ObjectId, &world, &right)
+    );
+    assert_eq!(
+        view(DebugView::MaterialId, &world, &left),
+        view(DebugView::MaterialId, &world, &right)
+    );
+
+    let miss: Ray = Ray::new(Point::new(0., 0., 5.), Vector::new(0., 0., -1.));
+    assert_eq!(view(DebugView::Uv, &world, &miss), Color::zeros());
+}
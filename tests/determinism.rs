@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    INFINITY,
+    camera::Camera,
+    color::Color,
+    hittable::{HittableList, Sphere},
+    integrator::{IntegratorConfig, PathIntegrator},
+    interval::Interval,
+    material::{Dielectric, Lambertian, Metal},
+    sampler::{Sampler, SobolSampler},
+    vector::{Point, Vector},
+};
+
+fn world() -> HittableList {
+    HittableList::new(vec![
+        Arc::new(Sphere::new(
+            Point::new(0., -1000., 0.),
+            1000.,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )),
+        Arc::new(Sphere::new(
+            Point::new(0.3, 1., 0.3),
+            1.,
+            Arc::new(Dielectric::new(1.5)),
+        )),
+        Arc::new(Sphere::new(
+            Point::new(2.4, 2., -1.2),
+            2.,
+            Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.3)),
+        )),
+    ])
+}
+
+fn camera() -> Camera {
+    let look_from: Point = Point::new(0.2, 2.6, 4.);
+    let look_at: Point = Point::new(0.6, 1.3, -0.6);
+    Camera::new(
+        16,
+        16. / 9.,
+        80.,
+        look_from,
+        look_at,
+        Vector::new(0., 1., 0.),
+        0.5,
+        (look_from - look_at).norm(),
+        4,
+    )
+}
+
+fn render(camera: &Camera, threads: usize) -> Vec<Color> {
+    let integrator = PathIntegrator::new(IntegratorConfig::new(10));
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap()
+        .install(|| camera.render_image(&world(), &integrator, Interval::new(0.001, INFINITY)))
+}
+
+#[test]
+fn same_seed_renders_identically_on_any_thread_count() {
+    let camera: Camera = camera().with_seed(7);
+    assert_eq!(render(&camera, 1), render(&camera, 4));
+
+    let sobol: Box<dyn Sampler> = Box::new(SobolSampler::new(8));
+    let camera: Camera = camera.with_sampler(sobol);
+    assert_eq!(render(&camera, 1), render(&camera, 3));
+}
+
+#[test]
+fn different_seeds_render_differently() {
+    let first: Vec<Color> = render(&camera().with_seed(1), 2);
+    let second: Vec<Color> = render(&camera().with_seed(2), 2);
+    assert_ne!(first, second);
+}
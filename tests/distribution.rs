@@ -0,0 +1,155 @@
+use rand::{SeedableRng, rngs::StdRng};
+use rand_distr::Distribution;
+
+use ray_tracer::{
+    PI,
+    distribution::{ConcentricDisk, CosineHemisphere, GgxVndf, UniformCone, UniformTriangle},
+    microfacet::TrowbridgeReitz,
+    vector::{Point, Vector},
+};
+
+const SAMPLES: usize = 200_000;
+
+/// Pearson's statistic over the bins with enough expected samples, pooling the
+/// rest into one bin, and its degrees of freedom.
+fn chi_square(observed: &[f64], expected: &[f64]) -> (f64, usize) {
+    let mut statistic: f64 = 0.;
+    let mut bins: usize = 0;
+    let (mut pooled_observed, mut pooled_expected) = (0., 0.);
+    for (observed, expected) in observed.iter().zip(expected) {
+        if *expected < 5. {
+            pooled_observed += observed;
+            pooled_expected += expected;
+        } else {
+            statistic += (observed - expected).powi(2) / expected;
+            bins += 1;
+        }
+    }
+    if pooled_expected >= 5. {
+        statistic += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
+        bins += 1;
+    } else {
+        assert!(pooled_observed < 10., "samples outside the support");
+    }
+    (statistic, bins - 1)
+}
+
+/// Fails when the statistic exceeds the 0.999 quantile of the chi-square
+/// distribution, using the Wilson-Hilferty approximation.
+fn assert_fits(observed: &[f64], expected: &[f64]) {
+    let (statistic, freedom) = chi_square(observed, expected);
+    let k: f64 = freedom as f64;
+    let critical: f64 = k * (1. - 2. / (9. * k) + 3.09 * (2. / (9. * k)).sqrt()).powi(3);
+    assert!(
+        statistic < critical,
+        "chi-square {statistic:.1} exceeds {critical:.1} with {freedom} degrees of freedom"
+    );
+}
+
+const THETA_BINS: usize = 16;
+const PHI_BINS: usize = 32;
+
+fn direction_bin(w: &Vector) -> usize {
+    let w: Vector = w.normalize();
+    let theta: usize = (((1. - w.z) / 2. * THETA_BINS as f64) as usize).min(THETA_BINS - 1);
+    let phi: f64 = w.y.atan2(w.x).rem_euclid(2. * PI);
+    let phi: usize = ((phi / (2. * PI) * PHI_BINS as f64) as usize).min(PHI_BINS - 1);
+    theta * PHI_BINS + phi
+}
+
+/// Bins directions by `cos(theta)` and `phi`, and integrates `pdf` over each bin.
+fn check_directions(samples: impl Iterator<Item = Vector>, pdf: impl Fn(&Vector) -> f64) {
+    let mut observed: Vec<f64> = vec![0.; THETA_BINS * PHI_BINS];
+    samples.for_each(|w| observed[direction_bin(&w)] += 1.);
+
+    const STEPS: usize = 8;
+    let cosine_step: f64 = 2. / (THETA_BINS * STEPS) as f64;
+    let phi_step: f64 = 2. * PI / (PHI_BINS * STEPS) as f64;
+    let mut expected: Vec<f64> = vec![0.; THETA_BINS * PHI_BINS];
+    for i in 0..THETA_BINS * STEPS {
+        for j in 0..PHI_BINS * STEPS {
+            let cosine: f64 = 1. - (i as f64 + 0.5) * cosine_step;
+            let sine: f64 = (1. - cosine.powi(2)).sqrt();
+            let phi: f64 = (j as f64 + 0.5) * phi_step;
+            let w: Vector = Vector::new(sine * phi.cos(), sine * phi.sin(), cosine);
+            expected[direction_bin(&w)] += pdf(&w) * cosine_step * phi_step * SAMPLES as f64;
+        }
+    }
+
+    assert_fits(&observed, &expected);
+}
+
+#[test]
+fn cosine_hemisphere() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let samples = (0..SAMPLES).map(|_| CosineHemisphere.sample(&mut rng));
+    check_directions(samples, |w| CosineHemisphere.pdf(w));
+}
+
+#[test]
+fn uniform_cone() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let cone = UniformCone::new(0.3);
+    let samples = (0..SAMPLES).map(|_| cone.sample(&mut rng));
+    check_directions(samples, |w| cone.pdf(w));
+}
+
+#[test]
+fn ggx_visible_normals() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let wo: Vector = Vector::new(0.6, -0.3, 0.5).normalize();
+    let vndf = GgxVndf::new(TrowbridgeReitz::new(0.4, 0.7), wo);
+    let samples = (0..SAMPLES).map(|_| vndf.sample(&mut rng));
+    check_directions(samples, |wm| vndf.pdf(wm));
+}
+
+const GRID: usize = 24;
+
+#[test]
+fn concentric_disk() {
+    let mut rng = StdRng::seed_from_u64(4);
+    // Bins of equal area in r² and phi.
+    let bin = |(x, y): (f64, f64)| -> usize {
+        let r_2: usize = (((x.powi(2) + y.powi(2)) * GRID as f64) as usize).min(GRID - 1);
+        let phi: f64 = y.atan2(x).rem_euclid(2. * PI);
+        r_2 * GRID + ((phi / (2. * PI) * GRID as f64) as usize).min(GRID - 1)
+    };
+
+    let mut observed: Vec<f64> = vec![0.; GRID * GRID];
+    (0..SAMPLES).for_each(|_| observed[bin(ConcentricDisk.sample(&mut rng))] += 1.);
+
+    let area: f64 = PI / (GRID * GRID) as f64;
+    let expected: Vec<f64> =
+        vec![ConcentricDisk.pdf((0., 0.)) * area * SAMPLES as f64; GRID * GRID];
+    assert_fits(&observed, &expected);
+}
+
+#[test]
+fn uniform_triangle() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let triangle = UniformTriangle::new(
+        Point::new(0., 0., 0.),
+        Point::new(1., 0., 0.),
+        Point::new(0., 1., 0.),
+    );
+    let bin = |point: Point| -> usize {
+        let x: usize = ((point.x * GRID as f64) as usize).min(GRID - 1);
+        let y: usize = ((point.y * GRID as f64) as usize).min(GRID - 1);
+        y * GRID + x
+    };
+
+    let mut observed: Vec<f64> = vec![0.; GRID * GRID];
+    (0..SAMPLES).for_each(|_| observed[bin(triangle.sample(&mut rng))] += 1.);
+
+    // Cells below the diagonal lie fully inside, cells on it half inside.
+    let cell: f64 = 1. / (GRID * GRID) as f64;
+    let density: f64 = triangle.pdf(&Point::origin()) * SAMPLES as f64;
+    let expected: Vec<f64> = (0..GRID * GRID)
+        .map(|index| match (index % GRID + index / GRID + 1).cmp(&GRID) {
+            std::cmp::Ordering::Less => density * cell,
+            std::cmp::Ordering::Equal => density * cell / 2.,
+            std::cmp::Ordering::Greater => 0.,
+        })
+        .collect();
+    assert_fits(&observed, &expected);
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    color::Color,
+    hittable::HitRecord,
+    material::{
+        AlphaMask, AlphaMode, Coated, Conductor, DiffuseLight, Lambertian, Material, Metal,
+        MixMaterial,
+    },
+    microfacet,
+    ray::Ray,
+    sampler::{IndependentSampler, Sampler},
+    texture::SolidColor,
+    vector::{Point, Vector},
+};
+
+const SAMPLES: usize = 100_000;
+
+fn incoming(angle: f64) -> Ray {
+    let direction: Vector = Vector::new(angle.to_radians().sin(), 0., -angle.to_radians().cos());
+    Ray::new(Point::origin() - direction, direction)
+}
+
+fn hit(material: Arc<dyn Material>) -> HitRecord {
+    HitRecord::new(Point::origin(), Vector::z(), material, 1., true)
+        .with_tangents(Vector::x(), Vector::y())
+}
+
+/// Mean attenuation of scattering off `material`, checking that no sample
+/// carries more light than arrived.
+fn albedo(material: Arc<dyn Material>, angle: f64) -> Color {
+    let hit_record: HitRecord = hit(material.clone());
+    let mut sampler = IndependentSampler::new(1);
+
+    (0..SAMPLES)
+        .filter_map(|_| {
+            // Mixes choose their child by hashing the ray, so vary its origin.
+            let ray: Ray = incoming(angle);
+            let ray: Ray = Ray::new(
+                ray.origin() - sampler.get_1d() * ray.direction(),
+                ray.direction(),
+            );
+            material.scatter(&ray, &hit_record, &mut sampler)
+        })
+        .map(|scattering| {
+            assert!(scattering.attenuation().max() <= 1. + 1e-9);
+            scattering.attenuation()
+        })
+        .sum::<Color>()
+        / SAMPLES as f64
+}
+
+#[test]
+fn mix_passes_furnace_test() {
+    let white: Arc<dyn Material> = Arc::new(Lambertian::new(Color::repeat(1.)));
+    let mirror: Arc<dyn Material> =
+        Arc::new(Conductor::new(Color::repeat(1.), Color::repeat(1e3), 0.5));
+    let mix: Arc<dyn Material> = Arc::new(MixMaterial::new(white.clone(), mirror.clone(), 0.4));
+
+    for angle in [0., 45., 75.] {
+        let expected: Color =
+            0.6 * albedo(white.clone(), angle) + 0.4 * albedo(mirror.clone(), angle);
+        let mixed: Color = albedo(mix.clone(), angle);
+        assert!(mixed.max() <= 1.);
+        assert!(
+            (mixed - expected).abs().max() < 0.01,
+            "{angle}: {mixed:?} {expected:?}"
+        );
+    }
+}
+
+#[test]
+fn mix_forwards_emission_and_lobes() {
+    let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(Color::repeat(2.)));
+    let white: Arc<dyn Material> = Arc::new(Lambertian::new(Color::repeat(1.)));
+    let mix: Arc<dyn Material> = Arc::new(MixMaterial::new(light, white.clone(), 0.25));
+
+    let ray: Ray = incoming(30.);
+    let hit_record: HitRecord = hit(mix.clone());
+    assert_eq!(mix.emitted(&ray, &hit_record), Color::repeat(1.5));
+
+    let direction: Vector = Vector::new(-0.3, 0.2, 0.9);
+    let eval: Color = mix.eval(&ray, &hit_record, &direction);
+    let pdf: f64 = mix.pdf(&ray, &hit_record, &direction);
+    assert!((eval - 0.25 * white.eval(&ray, &hit_record, &direction)).norm() < 1e-12);
+    assert!((pdf - 0.25 * white.pdf(&ray, &hit_record, &direction)).abs() < 1e-12);
+}
+
+#[test]
+fn mix_scatters_the_child_it_reports_as_opaque() {
+    // The first child is entirely cut out, the second is diffuse.
+    let mirror: Arc<dyn Material> = Arc::new(Metal::new(Color::repeat(1.), 0.));
+    let hidden: Arc<dyn Material> = Arc::new(AlphaMask::new(
+        mirror,
+        Arc::new(SolidColor::new(Color::zeros())),
+        AlphaMode::Threshold(0.5),
+    ));
+    let white: Arc<dyn Material> = Arc::new(Lambertian::new(Color::repeat(1.)));
+    let mix: Arc<dyn Material> = Arc::new(MixMaterial::new(hidden, white, 0.3));
+
+    let mut sampler = IndependentSampler::new(1);
+    let mut opaque: usize = 0;
+    for _ in 0..10_000 {
+        let (u, v) = sampler.get_2d();
+        let direction: Vector = Vector::new(u - 0.5, v - 0.5, -1.);
+        let ray: Ray = Ray::new(Point::origin() - direction, direction);
+        let hit_record: HitRecord = hit(mix.clone());
+        if !mix.is_opaque(&ray, &hit_record) {
+            continue;
+        }
+        opaque += 1;
+
+        // Only the diffuse child may shade a hit that was kept.
+        let mirrored: Vector = Vector::new(direction.x, direction.y, -direction.z);
+        let scattering = mix.scatter(&ray, &hit_record, &mut sampler).unwrap();
+        assert!((scattering.ray().direction() - mirrored).norm() > 1e-6);
+    }
+    assert!((opaque as f64 / 10_000. - 0.3).abs() < 0.02, "{opaque}");
+}
+
+#[test]
+fn coated_passes_furnace_test() {
+    const STEPS: usize = 10_000;
+    let refractive_index: f64 = 1.5;
+    let coated: Arc<dyn Material> = Arc::new(Coated::new(
+        Arc::new(Lambertian::new(Color::repeat(1.))),
+        refractive_index,
+    ));
+
+    // Share of cosine-weighted light leaving through the coat.
+    let escaping: f64 = (0..STEPS)
+        .map(|i| {
+            let cosine: f64 = (i as f64 + 0.5) / STEPS as f64;
+            2. * cosine * (1. - microfacet::fresnel_dielectric(cosine, refractive_index))
+        })
+        .sum::<f64>()
+        / STEPS as f64;
+
+    for angle in [0_f64, 45., 75.] {
+        let cosine: f64 = angle.to_radians().cos();
+        let reflected: f64 = microfacet::fresnel_dielectric(cosine, refractive_index);
+        let expected: f64 = reflected + (1. - reflected) * escaping;
+        let result: Color = albedo(coated.clone(), angle);
+        assert!(result.max() < 1.);
+        assert!(
+            (result.x - expected).abs() < 0.01,
+            "{angle}: {result:?} {expected}"
+        );
+    }
+}
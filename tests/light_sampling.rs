@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    color::Color,
+    hittable::{HitRecord, Hittable, HittableList, Sphere},
+    integrator::IntegratorConfig,
+    material::{DiffuseLight, Material, Principled},
+    ray::Ray,
+    sampler::IndependentSampler,
+    vector::{Point, Vector},
+};
+
+const SAMPLES: usize = 20_000;
+
+#[test]
+fn transmitted_rays_come_from_the_material() {
+    let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(Color::repeat(4.)));
+    let above: Arc<dyn Hittable> = Arc::new(Sphere::new(Point::new(1., 0., 3.), 1., light.clone()));
+    let below: Arc<dyn Hittable> = Arc::new(Sphere::new(Point::new(0., 0., -3.), 1., light));
+    let config: IntegratorConfig =
+        IntegratorConfig::new(8).with_lights(Arc::new(HittableList::new(vec![above, below])));
+
+    let material: Arc<dyn Material> = Arc::new(
+        Principled::new(Color::repeat(0.9))
+            .with_roughness(0.5)
+            .with_transmission(1.),
+    );
+    let hit_record: HitRecord =
+        HitRecord::new(Point::origin(), Vector::z(), material.clone(), 1., true)
+            .with_tangents(Vector::x(), Vector::y());
+    let direction: Vector = Vector::new(0.3, 0., -1.);
+    let ray: Ray = Ray::new(Point::origin() - direction, direction);
+    let mut sampler = IndependentSampler::new(1);
+
+    let mut light_sampled: usize = 0;
+    for _ in 0..SAMPLES {
+        let Some(scattering) = material.scatter(&ray, &hit_record, &mut sampler) else {
+            continue;
+        };
+        let scattered: Vector = scattering.ray().direction();
+        let Some((next, _)) = config.next_ray(&ray, &hit_record, scattering, &mut sampler) else {
+            continue;
+        };
+        if next.direction().z < 0. {
+            // Only the material knows the media behind the surface.
+            assert_eq!(next.direction(), scattered);
+        } else if next.direction() != scattered {
+            light_sampled += 1;
+        }
+    }
+    assert!(light_sampled > SAMPLES / 10, "{light_sampled}");
+}
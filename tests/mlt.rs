@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    INFINITY,
+    camera::Camera,
+    color::Color,
+    hittable::{HittableList, Sphere},
+    integrator::{IntegratorConfig, PathIntegrator},
+    interval::Interval,
+    material::{Dielectric, Lambertian},
+    mlt::{MltRenderer, MltSampler},
+    sampler::Sampler,
+    vector::{Point, Vector},
+};
+
+fn draw(sampler: &mut MltSampler) -> Vec<f64> {
+    sampler.start_pixel_sample((0, 0), 0);
+    let (u, v) = sampler.get_pixel_2d();
+    let mut values: Vec<f64> = vec![u, v];
+    values.extend((0..6).map(|_| sampler.get_1d()));
+    values
+}
+
+#[test]
+fn rejected_mutations_are_undone() {
+    // Without small-step noise, small steps leave the sample as it is.
+    let mut sampler = MltSampler::new((16, 16), 0., 0.5);
+    sampler.set_seed(3);
+    let initial: Vec<f64> = draw(&mut sampler);
+    assert!(initial.iter().all(|value| (0. ..1.).contains(value)));
+
+    let mut large_steps: Vec<Vec<f64>> = Vec::new();
+    for _ in 0..40 {
+        sampler.start_iteration();
+        let values: Vec<f64> = draw(&mut sampler);
+        if values != initial {
+            assert!(!large_steps.contains(&values));
+            large_steps.push(values);
+        }
+        sampler.reject();
+    }
+    assert!(!large_steps.is_empty() && large_steps.len() < 40);
+}
+
+#[test]
+fn matches_path_tracing() {
+    let world = HittableList::new(vec![
+        Arc::new(Sphere::new(
+            Point::new(0., -1000., 0.),
+            1000.,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )),
+        Arc::new(Sphere::new(
+            Point::new(0., 1., 0.),
+            1.,
+            Arc::new(Dielectric::new(1.5)),
+        )),
+    ]);
+    let camera: Camera = Camera::new(
+        8,
+        2.,
+        40.,
+        Point::new(0., 2., 5.),
+        Point::new(0., 0.5, 0.),
+        Vector::new(0., 1., 0.),
+        0.,
+        1.,
+        256,
+    )
+    .with_seed(5);
+    let integrator = PathIntegrator::new(IntegratorConfig::new(8));
+    let time: Interval = Interval::new(0.001, INFINITY);
+
+    let mean = |image: &[Color]| image.iter().sum::<Color>() / image.len() as f64;
+    let path: Color = mean(&camera.render_image(&world, &integrator, time));
+    let renderer: MltRenderer = MltRenderer::new(64).with_bootstrap_samples(20_000);
+    let image: Vec<Color> = renderer.render(&camera, &world, &integrator, time);
+    assert_eq!(image, renderer.render(&camera, &world, &integrator, time));
+
+    let mlt: Color = mean(&image);
+    assert!(
+        (mlt - path).norm() / path.norm() < 0.05,
+        "{mlt:?} differs from {path:?}"
+    );
+}
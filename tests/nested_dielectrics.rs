@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    INFINITY,
+    color::Color,
+    hittable::{HitRecord, Hittable, HittableList, Sphere},
+    interval::Interval,
+    material::{Dielectric, Material},
+    medium::{MediumEntry, MediumStack},
+    ray::Ray,
+    sampler::IndependentSampler,
+    vector::{Point, Vector},
+};
+
+#[test]
+fn relative_index_applies_between_liquid_and_glass() {
+    let glass: Arc<dyn Material> = Arc::new(Dielectric::new(1.5).with_priority(2));
+    let liquid: Arc<dyn Material> = Arc::new(Dielectric::new(1.33).with_priority(1));
+    let media: MediumStack = MediumStack::new()
+        .pushed(MediumEntry::new(1, 2, 1.5, Color::zeros()))
+        .pushed(MediumEntry::new(2, 1, 1.33, Color::zeros()));
+
+    // Leaving the inner wall of the glass into the liquid the glass overlaps.
+    let angle: f64 = 30_f64.to_radians();
+    let direction: Vector = Vector::new(angle.sin(), 0., angle.cos());
+    let ray: Ray = Ray::new(Point::origin() - direction, direction).with_media(media);
+    let wall: HitRecord =
+        HitRecord::new(Point::origin(), -Vector::z(), glass.clone(), 1., false).with_object_id(1);
+
+    let mut sampler = IndependentSampler::new(1);
+    let mut refracted: usize = 0;
+    for _ in 0..1_000 {
+        let scattering = glass.scatter(&ray, &wall, &mut sampler).unwrap();
+        let out: Vector = scattering.ray().direction().normalize();
+        if out.z > 0. {
+            assert!((out.x - angle.sin() * 1.5 / 1.33).abs() < 1e-9);
+            assert!(!scattering.ray().media().contains(1));
+            assert!(scattering.ray().media().contains(2));
+            refracted += 1;
+        }
+    }
+    assert!(refracted > 900);
+
+    // The front face of the liquid inside the glass is not an interface.
+    let media: MediumStack = MediumStack::new().pushed(MediumEntry::new(1, 2, 1.5, Color::zeros()));
+    let ray: Ray = Ray::new(Point::origin() - direction, direction).with_media(media);
+    let surface: HitRecord =
+        HitRecord::new(Point::origin(), -Vector::z(), liquid.clone(), 1., true).with_object_id(2);
+    let scattering = liquid.scatter(&ray, &surface, &mut sampler).unwrap();
+    assert_eq!(scattering.ray().direction(), direction);
+    assert!(scattering.ray().media().contains(2));
+}
+
+#[test]
+fn objects_sharing_a_dielectric_are_tracked_apart() {
+    let ice: Arc<Dielectric> = Arc::new(Dielectric::new(1.31));
+    let first: Arc<Sphere> = Arc::new(Sphere::new(Point::new(0., 0., 0.), 1., ice.clone()));
+    let second: Arc<Sphere> = Arc::new(Sphere::new(Point::new(1.5, 0., 0.), 1., ice));
+    let world = HittableList::new(vec![first, second]);
+
+    // Through the first cube, the overlap and the second cube along the x axis.
+    let mut ray: Ray = Ray::new(Point::new(-5., 0., 0.), Vector::x());
+    let mut sampler = IndependentSampler::new(1);
+    let mut crossings: Vec<(usize, MediumStack)> = Vec::new();
+    while let Some(hit_record) = world.hit(&ray, Interval::new(0.001, INFINITY)) {
+        let scattering = hit_record
+            .material()
+            .scatter(&ray, &hit_record, &mut sampler)
+            .unwrap();
+        if scattering.ray().direction().x > 0. {
+            crossings.push((hit_record.object_id(), *scattering.ray().media()));
+            ray = scattering.ray().clone();
+        }
+    }
+
+    let ids: Vec<usize> = crossings.iter().map(|(id, _)| *id).collect();
+    let (first, second) = (ids[0], ids[1]);
+    assert_ne!(first, second);
+    assert_eq!(ids, vec![first, second, first, second]);
+
+    // Leaving the first cube inside the second one keeps the second one.
+    let media: &MediumStack = &crossings[2].1;
+    assert!(!media.contains(first) && media.contains(second));
+    assert!(crossings[3].1.is_empty());
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    color::Color,
+    hittable::{HitRecord, Hittable, Perturbed, Sphere},
+    interval::Interval,
+    material::{Dielectric, Lambertian, Material},
+    ray::Ray,
+    sampler::{IndependentSampler, Sampler},
+    texture::{BumpMap, NormalMap, NormalPerturbation, SolidColor, Texture},
+    vector::{Point, Vector},
+};
+
+/// Height rising linearly along u.
+struct Ramp;
+
+impl Texture for Ramp {
+    fn value(&self, u: f64, _v: f64, _point: &Point) -> Color {
+        Color::repeat(u)
+    }
+}
+
+fn flat_hit(material: Arc<dyn Material>) -> HitRecord {
+    HitRecord::new(Point::origin(), Vector::z(), material, 1., true)
+        .with_uv(0.5, 0.5)
+        .with_tangents(Vector::x(), Vector::y())
+}
+
+#[test]
+fn flat_normal_map_leaves_shading_unchanged() {
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(Point::new(0., 0., -2.), 1., material));
+    let flat = Arc::new(SolidColor::new(Color::new(0.5, 0.5, 1.)));
+    let mapped = Perturbed::new(sphere.clone(), Arc::new(NormalMap::new(flat)));
+
+    for (x, y) in [(0., 0.), (0.3, -0.2), (-0.5, 0.4), (0.1, 0.7)] {
+        let ray: Ray = Ray::new(Point::new(x, y, 0.), Vector::new(0., 0., -1.));
+        let plain: HitRecord = sphere
+            .hit(&ray, Interval::new(0.001, f64::INFINITY))
+            .unwrap();
+        let bumped: HitRecord = mapped
+            .hit(&ray, Interval::new(0.001, f64::INFINITY))
+            .unwrap();
+        assert!((bumped.shading_normal() - plain.normal()).norm() < 1e-9);
+
+        let mut plain_sampler = IndependentSampler::new(1);
+        let mut bumped_sampler = IndependentSampler::new(1);
+        plain_sampler.set_seed(7);
+        bumped_sampler.set_seed(7);
+        let plain_out = plain
+            .material()
+            .scatter(&ray, &plain, &mut plain_sampler)
+            .unwrap();
+        let bumped_out = bumped
+            .material()
+            .scatter(&ray, &bumped, &mut bumped_sampler)
+            .unwrap();
+        assert!((plain_out.ray().direction() - bumped_out.ray().direction()).norm() < 1e-9);
+        assert_eq!(plain_out.attenuation(), bumped_out.attenuation());
+    }
+}
+
+#[test]
+fn bump_map_tilts_away_from_rising_height() {
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let hit_record: HitRecord = flat_hit(material);
+
+    let normal: Vector = BumpMap::new(Arc::new(Ramp), 1.).perturb(&hit_record);
+    let expected: Vector = Vector::new(-1., 0., 1.).normalize();
+    assert!((normal - expected).norm() < 1e-3, "{normal:?}");
+
+    // A flat height field leaves the normal alone.
+    let flat = Arc::new(SolidColor::new(Color::new(0.3, 0.3, 0.3)));
+    let normal: Vector = BumpMap::new(flat, 1.).perturb(&hit_record);
+    assert!((normal - Vector::z()).norm() < 1e-9);
+}
+
+#[test]
+fn dielectric_stays_on_the_geometric_side() {
+    let material: Arc<dyn Material> = Arc::new(Dielectric::new(1.5));
+    let mut hit_record: HitRecord = flat_hit(material.clone());
+    hit_record.set_shading_normal(Vector::new(0.2, 0., 1.).normalize());
+
+    let direction: Vector = Vector::new(1., 0., -0.3);
+    let ray: Ray = Ray::new(Point::origin() - direction, direction);
+    let mut sampler = IndependentSampler::new(1);
+    for _ in 0..10_000 {
+        let Some(scattering) = material.scatter(&ray, &hit_record, &mut sampler) else {
+            continue;
+        };
+        let refracted: bool = !scattering.ray().media().is_empty();
+        let below: bool = scattering.ray().direction().dot(&hit_record.normal()) < 0.;
+        assert_eq!(refracted, below);
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    color::Color,
+    hittable::HitRecord,
+    material::{Lambertian, Material, OrenNayar},
+    ray::Ray,
+    sampler::IndependentSampler,
+    vector::{Point, Vector},
+};
+
+const SAMPLES: usize = 20_000;
+
+fn mean_attenuation(material: Arc<dyn Material>, angle: f64) -> Color {
+    let direction: Vector = Vector::new(angle.to_radians().sin(), 0., -angle.to_radians().cos());
+    let ray: Ray = Ray::new(Point::origin() - direction, direction);
+    let hit_record: HitRecord =
+        HitRecord::new(Point::origin(), Vector::z(), material.clone(), 1., true);
+    let mut sampler = IndependentSampler::new(1);
+
+    (0..SAMPLES)
+        .filter_map(|_| material.scatter(&ray, &hit_record, &mut sampler))
+        .map(|scattering| {
+            assert!(scattering.ray().direction().z > 0.);
+            scattering.attenuation()
+        })
+        .sum::<Color>()
+        / SAMPLES as f64
+}
+
+#[test]
+fn reduces_to_lambertian_at_zero_roughness() {
+    let albedo: Color = Color::new(0.8, 0.5, 0.2);
+    let direction: Vector = Vector::new(0.6, 0., -0.8);
+    let ray: Ray = Ray::new(Point::origin() - direction, direction);
+    let material: Arc<dyn Material> = Arc::new(OrenNayar::new(albedo, 0.));
+    let hit_record: HitRecord =
+        HitRecord::new(Point::origin(), Vector::z(), material.clone(), 1., true);
+    let mut sampler = IndependentSampler::new(1);
+
+    for _ in 0..SAMPLES {
+        let scattering = material.scatter(&ray, &hit_record, &mut sampler).unwrap();
+        assert!((scattering.attenuation() - albedo).norm() < 1e-12);
+    }
+
+    for angle in [0., 45., 80.] {
+        let oren_nayar: Color = mean_attenuation(Arc::new(OrenNayar::new(albedo, 0.)), angle);
+        let lambertian: Color = mean_attenuation(Arc::new(Lambertian::new(albedo)), angle);
+        assert!((oren_nayar - lambertian).norm() < 1e-12);
+    }
+}
+
+#[test]
+fn roughness_flattens_the_response() {
+    let albedo: Color = Color::repeat(1.);
+    for angle in [0., 45., 80.] {
+        let reflected: Color = mean_attenuation(Arc::new(OrenNayar::new(albedo, 30.)), angle);
+        assert!(reflected.max() <= 1.);
+    }
+    // Rough surfaces lose light at normal incidence but brighten towards grazing angles.
+    let normal: Color = mean_attenuation(Arc::new(OrenNayar::new(albedo, 30.)), 0.);
+    let grazing: Color = mean_attenuation(Arc::new(OrenNayar::new(albedo, 30.)), 80.);
+    assert!(normal.x < 1. && grazing.x > normal.x);
+}
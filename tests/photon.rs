@@ -0,0 +1,151 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::sync::Arc;
+
+use ray_tracer::{
+    INFINITY,
+    camera::Camera,
+    color::Color,
+    hittable::{Hittable, HittableList, Sphere},
+    integrator::{Integrator, IntegratorConfig, PathIntegrator},
+    interval::Interval,
+    material::{Dielectric, DiffuseLight, Lambertian},
+    photon::{Photon, PhotonIntegrator, PhotonMap},
+    ray::Ray,
+    sampler::{IndependentSampler, Sampler},
+    vector::{Point, Vector},
+};
+
+#[test]
+fn nearest_matches_brute_force() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let photons: Vec<Photon> = (0..5000)
+        .map(|_| {
+            // Flattened like photons on a surface.
+            let point: Point = Point::new(rng.random(), rng.random(), 0.1 * rng.random::<f64>());
+            Photon::new(point, -Vector::z(), Color::repeat(1.))
+        })
+        .collect();
+    let map = PhotonMap::new(photons.clone());
+    assert_eq!(map.len(), photons.len());
+
+    for _ in 0..200 {
+        let point: Point = Point::new(rng.random(), rng.random(), rng.random());
+        let (found, radius_2) = map.nearest(&point, 20, 0.3);
+
+        let mut expected: Vec<f64> = photons
+            .iter()
+            .map(|photon| (photon.point() - point).norm_squared())
+            .filter(|distance_2| *distance_2 < 0.09)
+            .collect();
+        expected.sort_by(f64::total_cmp);
+        expected.truncate(20);
+
+        let mut distances: Vec<f64> = found
+            .iter()
+            .map(|photon| (photon.point() - point).norm_squared())
+            .collect();
+        distances.sort_by(f64::total_cmp);
+
+        assert_eq!(distances, expected);
+        match expected.len() {
+            20 => assert_eq!(radius_2, expected[19]),
+            _ => assert_eq!(radius_2, 0.09),
+        }
+    }
+}
+
+fn light() -> Arc<dyn Hittable> {
+    Arc::new(Sphere::new(
+        Point::new(0.4, 4., -2.5),
+        1.2,
+        Arc::new(DiffuseLight::new(Color::repeat(5.))),
+    ))
+}
+
+/// A glass ball focusing a light onto the floor of a dark room.
+fn caustic_scene() -> HittableList {
+    HittableList::new(vec![
+        Arc::new(Sphere::new(
+            Point::new(0., -1000., 0.),
+            1000.,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )),
+        Arc::new(Sphere::new(
+            Point::new(0.4, 1., -0.6),
+            0.8,
+            Arc::new(Dielectric::new(1.5)),
+        )),
+        Arc::new(Sphere::new(
+            Point::origin(),
+            20.,
+            Arc::new(Lambertian::new(Color::repeat(0.05))),
+        )),
+        light(),
+    ])
+}
+
+/// Mean pixel around the spot where the ball focuses the light; the caustic
+/// makes up most of it.
+fn mean_pixel(integrator: &dyn Integrator, samples_per_pixel: i32) -> Color {
+    let world: HittableList = caustic_scene();
+    let camera: Camera = Camera::new(
+        8,
+        2.,
+        20.,
+        Point::new(0.2, 2.6, 4.),
+        Point::new(0.4, 0., 0.1),
+        Vector::new(0., 1., 0.),
+        0.,
+        1.,
+        samples_per_pixel,
+    );
+    let image: Vec<Color> = camera.render_image(&world, integrator, Interval::new(0.001, INFINITY));
+    image.iter().sum::<Color>() / image.len() as f64
+}
+
+#[test]
+fn agrees_with_path_tracing() {
+    let lights: Arc<HittableList> = Arc::new(HittableList::new(vec![light()]));
+    let config: IntegratorConfig = IntegratorConfig::new(4).with_lights(lights);
+    let path: Color = mean_pixel(
+        &PathIntegrator::new(config.clone()).with_roulette_depth(4),
+        4096,
+    );
+    let photon: Color = mean_pixel(
+        &PhotonIntegrator::new(config, 200_000).with_gather(50, 0.05),
+        256,
+    );
+
+    assert!(
+        ((photon - path).norm() / path.norm()) < 0.03,
+        "{photon:?} differs from {path:?}"
+    );
+}
+
+#[test]
+fn photon_map_follows_the_seed() {
+    let world: HittableList = caustic_scene();
+    let lights: Arc<HittableList> = Arc::new(HittableList::new(vec![light()]));
+    let integrator = PhotonIntegrator::new(IntegratorConfig::new(4).with_lights(lights), 20_000)
+        .with_gather(50, 0.05);
+    let time: Interval = Interval::new(0.001, INFINITY);
+
+    // A ray onto the caustic below the ball, traced the same way every time.
+    let caustic = |seed: u64| -> Color {
+        integrator.prepare(&world, time, false, seed);
+        let from: Point = Point::new(0.4, 2.6, 3.);
+        let ray: Ray = Ray::new(from, Point::new(0.4, 0., -0.1) - from);
+        let mut sampler = IndependentSampler::new(1);
+        (0..64)
+            .map(|index| {
+                sampler.start_pixel_sample((0, 0), index);
+                integrator.radiance(&ray, &world, time, &mut sampler)
+            })
+            .sum::<Color>()
+    };
+
+    let first: Color = caustic(1);
+    assert!(first.norm() > 0.);
+    assert_eq!(caustic(1), first);
+    assert_ne!(caustic(2), first);
+}
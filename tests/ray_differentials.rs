@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    camera::Camera,
+    color::Color,
+    hittable::{HitRecord, SurfaceDifferentials},
+    material::{Lambertian, Material},
+    ray::Ray,
+    sampler::IndependentSampler,
+    texture::{ImageTexture, Texture},
+    vector::{Point, Vector},
+};
+
+const WIDTH: i32 = 100;
+
+/// Hit on the plane `z = -distance`, facing the camera at the origin.
+fn plane_hit(ray: &Ray, distance: f64) -> HitRecord {
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let t: f64 = -distance / ray.direction().z;
+    HitRecord::new(ray.at(t), Vector::z(), material, t, true)
+        .with_tangents(Vector::x(), Vector::y())
+}
+
+fn pinhole(samples_per_pixel: i32) -> Camera {
+    Camera::new(
+        WIDTH,
+        1.,
+        90.,
+        Point::origin(),
+        Point::new(0., 0., -1.),
+        Vector::y(),
+        0.,
+        1.,
+        samples_per_pixel,
+    )
+}
+
+fn footprint(camera: &Camera, distance: f64) -> SurfaceDifferentials {
+    let mut sampler = IndependentSampler::new(1);
+    let ray: Ray = camera.get_ray(WIDTH / 2, WIDTH / 2, &mut sampler);
+    plane_hit(&ray, distance).differentials(&ray).unwrap()
+}
+
+#[test]
+fn pinhole_differentials_match_pixel_spacing() {
+    // A 90 degree field of view spans two units at unit distance.
+    let spacing: f64 = 2. / f64::from(WIDTH);
+
+    for distance in [1., 3.] {
+        let differentials: SurfaceDifferentials = footprint(&pinhole(1), distance);
+        let expected_x: Vector = Vector::new(spacing * distance, 0., 0.);
+        let expected_y: Vector = Vector::new(0., -spacing * distance, 0.);
+        assert!((differentials.dpdx - expected_x).norm() < 1e-9);
+        assert!((differentials.dpdy - expected_y).norm() < 1e-9);
+    }
+
+    // Four samples per pixel halve the footprint of each.
+    let differentials: SurfaceDifferentials = footprint(&pinhole(4), 1.);
+    assert!((differentials.dpdx.x - spacing / 2.).abs() < 1e-9);
+}
+
+#[test]
+fn wider_footprint_picks_coarser_mip_level() {
+    // A black and white checkerboard averages to grey from the first level up.
+    let pixels: Vec<Color> = (0..64)
+        .map(|i| {
+            if (i % 8 + i / 8) % 2 == 0 {
+                Color::zeros()
+            } else {
+                Color::new(1., 1., 1.)
+            }
+        })
+        .collect();
+    let texture = ImageTexture::new(8, 8, pixels);
+    assert_eq!(texture.levels(), 4);
+
+    // The centre of the first texel, where the finest level is pure black.
+    let (u, v): (f64, f64) = (1. / 16., 15. / 16.);
+    let point: Point = Point::origin();
+
+    let contrast =
+        |width: f64| -> f64 { (texture.filtered_value(u, v, &point, width / 8.).x - 0.5).abs() };
+    assert!(contrast(1.) > 0.49);
+    assert!(contrast(1.5) < contrast(1.) && contrast(1.5) > 0.01);
+    assert!(contrast(2.) < 1e-9);
+    assert!(contrast(64.) < 1e-9);
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    INFINITY,
+    camera::Camera,
+    color::Color,
+    hittable::{Hittable, HittableList, Sphere},
+    integrator::{Integrator, IntegratorConfig, RecursiveIntegrator, sky_box},
+    interval::Interval,
+    material::{Dielectric, Lambertian, Metal},
+    ray::Ray,
+    sampler::{IndependentSampler, Sampler},
+    spectrum,
+    vector::{Point, Vector},
+};
+
+const MAX_DEPTH: i32 = 10;
+
+/// The colorizer the camera used before integrators were introduced.
+fn colorizer(
+    ray: &Ray,
+    world: &HittableList,
+    time: Interval,
+    depth: i32,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    if depth <= 0 {
+        return Color::new(0., 0., 0.);
+    }
+
+    world
+        .hit(ray, time)
+        .map(|hit_record| {
+            let transmittance: Color = spectrum::project(
+                ray.media()
+                    .transmittance(hit_record.t() * ray.direction().norm()),
+                ray.wavelength(),
+            );
+
+            let radiance: Color = hit_record
+                .material()
+                .scatter(ray, &hit_record, sampler)
+                .map(|scattering| {
+                    spectrum::project(scattering.attenuation(), ray.wavelength()).component_mul(
+                        &colorizer(scattering.ray(), world, time, depth - 1, sampler),
+                    )
+                })
+                .unwrap_or(Color::new(0., 0., 0.));
+
+            transmittance.component_mul(&radiance)
+        })
+        .unwrap_or(spectrum::project(sky_box(ray), ray.wavelength()))
+}
+
+fn world() -> HittableList {
+    let objects: Vec<Arc<dyn Hittable>> = vec![
+        Arc::new(Sphere::new(
+            Point::new(0., -1000., 0.),
+            1000.,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )),
+        Arc::new(Sphere::new(
+            Point::new(0., 1., 0.),
+            1.,
+            Arc::new(Dielectric::new(1.5).with_absorption(Color::new(0.1, 0.3, 0.5))),
+        )),
+        Arc::new(Sphere::new(
+            Point::new(-2.2, 1., 0.),
+            1.,
+            Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1))),
+        )),
+        Arc::new(Sphere::new(
+            Point::new(2.2, 1., 0.),
+            1.,
+            Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.1)),
+        )),
+    ];
+    HittableList::new(objects)
+}
+
+#[test]
+fn matches_the_former_colorizer() {
+    let world: HittableList = world();
+    let time: Interval = Interval::new(0.001, INFINITY);
+    let camera = Camera::new(
+        32,
+        16. / 9.,
+        30.,
+        Point::new(6., 2., 8.),
+        Point::new(0., 1., 0.),
+        Vector::y(),
+        0.6,
+        10.,
+        4,
+    );
+    let integrator = RecursiveIntegrator::new(IntegratorConfig::new(MAX_DEPTH));
+
+    let mut sampler = IndependentSampler::new(4);
+    let mut former = IndependentSampler::new(4);
+    sampler.set_seed(42);
+    former.set_seed(42);
+
+    for j in 0..18 {
+        for i in 0..32 {
+            for index in 0..4 {
+                sampler.start_pixel_sample((i, j), index);
+                former.start_pixel_sample((i, j), index);
+                let ray: Ray = camera.get_ray(i, j, &mut sampler);
+                assert_eq!(
+                    ray.direction(),
+                    camera.get_ray(i, j, &mut former).direction()
+                );
+
+                let radiance: Color = integrator.radiance(&ray, &world, time, &mut sampler);
+                let expected: Color = colorizer(&ray, &world, time, MAX_DEPTH, &mut former);
+                assert_eq!(radiance, expected, "pixel ({i}, {j}), sample {index}");
+            }
+        }
+    }
+}
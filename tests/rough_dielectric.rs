@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    hittable::HitRecord,
+    material::{Dielectric, Material, RoughDielectric},
+    microfacet,
+    ray::Ray,
+    sampler::IndependentSampler,
+    vector::{Point, Vector},
+};
+
+const SAMPLES: usize = 50_000;
+
+/// A ray hitting the origin at `angle` from the normal, travelling down onto a
+/// front face or up onto a back face.
+fn incoming(angle: f64, front_face: bool) -> Ray {
+    let down: f64 = if front_face { -1. } else { 1. };
+    let direction: Vector = Vector::new(
+        angle.to_radians().sin(),
+        0.,
+        down * angle.to_radians().cos(),
+    );
+    Ray::new(Point::origin() - direction, direction)
+}
+
+fn hit(material: Arc<dyn Material>, front_face: bool) -> HitRecord {
+    let normal: Vector = if front_face {
+        Vector::z()
+    } else {
+        -Vector::z()
+    };
+    HitRecord::new(Point::origin(), normal, material, 1., front_face)
+        .with_tangents(Vector::x(), Vector::y())
+}
+
+/// Fraction of samples reflected back to the side of the incoming ray, and their
+/// mean throughput.
+fn statistics(material: Arc<dyn Material>, angle: f64, front_face: bool) -> (f64, f64) {
+    let ray: Ray = incoming(angle, front_face);
+    let hit_record: HitRecord = hit(material.clone(), front_face);
+    let mut sampler = IndependentSampler::new(1);
+
+    let (reflected, throughput) = (0..SAMPLES)
+        .filter_map(|_| material.scatter(&ray, &hit_record, &mut sampler))
+        .fold((0, 0.), |(reflected, throughput), scattering| {
+            assert!(scattering.attenuation().max() <= 1. + 1e-9);
+            let back: bool = scattering.ray().direction().dot(&hit_record.normal()) > 0.;
+            (
+                reflected + usize::from(back),
+                throughput + scattering.attenuation().max(),
+            )
+        });
+
+    (
+        reflected as f64 / SAMPLES as f64,
+        throughput / SAMPLES as f64,
+    )
+}
+
+#[test]
+fn never_gains_energy() {
+    for front_face in [true, false] {
+        for angle in [0., 30., 60., 85.] {
+            // Single scattering loses more energy to masking the rougher the surface.
+            let mut previous: f64 = 1.;
+            for roughness in [0.1, 0.3, 0.6, 1.] {
+                let material: Arc<dyn Material> = Arc::new(RoughDielectric::new(1.5, roughness));
+                let (reflected, throughput) = statistics(material, angle, front_face);
+                assert!(
+                    reflected > 0. && throughput > 0. && throughput < previous,
+                    "front face {front_face}, roughness {roughness}, angle {angle}: {throughput}"
+                );
+                previous = throughput;
+            }
+        }
+    }
+}
+
+#[test]
+fn nearly_smooth_albedo_matches_fresnel() {
+    for front_face in [true, false] {
+        let eta: f64 = if front_face { 1.5 } else { 1. / 1.5 };
+        // From inside, 60 degrees is past the critical angle and reflects totally.
+        for angle in [0., 30., 60.] {
+            let material: Arc<dyn Material> = Arc::new(RoughDielectric::new(1.5, 0.05));
+            let (reflected, throughput) = statistics(material, angle, front_face);
+            let fresnel: f64 = microfacet::fresnel_dielectric(angle.to_radians().cos(), eta);
+            assert!(
+                (reflected - fresnel).abs() < 0.01 && (throughput - 1.).abs() < 0.01,
+                "front face {front_face}, angle {angle}: {reflected} vs {fresnel}, {throughput}"
+            );
+        }
+    }
+}
+
+#[test]
+fn matches_smooth_dielectric_at_zero_roughness() {
+    // Beyond ~30 degrees Schlick's approximation drifts from the exact Fresnel term.
+    for angle in [0., 20., 30.] {
+        let rough: Arc<dyn Material> = Arc::new(RoughDielectric::new(1.5, 0.));
+        let smooth: Arc<dyn Material> = Arc::new(Dielectric::new(1.5));
+
+        let (rough_reflected, rough_throughput) = statistics(rough, angle, true);
+        let (smooth_reflected, smooth_throughput) = statistics(smooth, angle, true);
+
+        assert!((rough_throughput - smooth_throughput).abs() < 1e-9);
+        assert!(
+            (rough_reflected - smooth_reflected).abs() < 6e-3,
+            "angle {angle}: {rough_reflected} vs {smooth_reflected}"
+        );
+    }
+}
+
+#[test]
+fn zero_roughness_scatters_like_a_perfect_interface() {
+    let rough: Arc<dyn Material> = Arc::new(RoughDielectric::new(1.5, 0.));
+    let ray: Ray = incoming(40., true);
+    let unit_in: Vector = ray.direction().normalize();
+    let mirrored: Vector = Vector::new(unit_in.x, unit_in.y, -unit_in.z);
+    let sine_out: f64 = 40_f64.to_radians().sin() / 1.5;
+
+    let hit_record: HitRecord = hit(rough.clone(), true);
+    let mut sampler = IndependentSampler::new(1);
+    for _ in 0..1_000 {
+        let scattering = rough.scatter(&ray, &hit_record, &mut sampler).unwrap();
+        let out: Vector = scattering.ray().direction().normalize();
+        if out.z > 0. {
+            assert!((out - mirrored).norm() < 1e-9);
+        } else {
+            assert!((out.x - sine_out).abs() < 1e-9);
+        }
+    }
+}
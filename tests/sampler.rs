@@ -0,0 +1,111 @@
+use ray_tracer::sampler::{HaltonSampler, Sampler, SobolSampler, StratifiedSampler};
+
+fn samplers() -> Vec<Box<dyn Sampler>> {
+    vec![
+        Box::new(StratifiedSampler::new(4, 4, true)),
+        Box::new(HaltonSampler::new(16)),
+        Box::new(SobolSampler::new(16)),
+    ]
+}
+
+/// The first `dimensions` 2D samples of every sample of `pixel`.
+fn pixel_samples(
+    sampler: &mut dyn Sampler,
+    pixel: (i32, i32),
+    dimensions: usize,
+) -> Vec<Vec<(f64, f64)>> {
+    (0..sampler.samples_per_pixel())
+        .map(|index| {
+            sampler.start_pixel_sample(pixel, index);
+            (0..dimensions).map(|_| sampler.get_2d()).collect()
+        })
+        .collect()
+}
+
+/// Whether exactly one of `points` falls in each cell of a `columns` by `rows` grid.
+fn stratified(points: &[(f64, f64)], columns: usize, rows: usize) -> bool {
+    let mut counts: Vec<usize> = vec![0; columns * rows];
+    for (x, y) in points {
+        counts[(y * rows as f64) as usize * columns + (x * columns as f64) as usize] += 1;
+    }
+    counts.iter().all(|&count| count == 1)
+}
+
+#[test]
+fn samples_stay_in_the_unit_interval() {
+    for mut sampler in samplers() {
+        for pixel in [(0, 0), (17, -3), (-1000, 5000)] {
+            for sample in pixel_samples(sampler.as_mut(), pixel, 40) {
+                for (x, y) in sample {
+                    assert!((0. ..1.).contains(&x) && (0. ..1.).contains(&y));
+                }
+            }
+            sampler.start_pixel_sample(pixel, 3);
+            assert!((0..100).all(|_| (0. ..1.).contains(&sampler.get_1d())));
+        }
+    }
+}
+
+#[test]
+fn pixel_samples_are_stratified() {
+    // Strata of the stratified sampler and elementary intervals of the
+    // (0, 4, 2)-net formed by 16 Sobol points.
+    for mut sampler in [
+        Box::new(StratifiedSampler::new(4, 4, true)) as Box<dyn Sampler>,
+        Box::new(SobolSampler::new(16)),
+    ] {
+        for dimension in 0..8 {
+            let points: Vec<(f64, f64)> = pixel_samples(sampler.as_mut(), (5, 9), 8)
+                .iter()
+                .map(|sample| sample[dimension])
+                .collect();
+            assert!(stratified(&points, 4, 4));
+        }
+
+        let values: Vec<(f64, f64)> = (0..16)
+            .map(|index| {
+                sampler.start_pixel_sample((5, 9), index);
+                (sampler.get_1d(), 0.)
+            })
+            .collect();
+        assert!(stratified(&values, 16, 1));
+    }
+
+    // 72 Halton points in bases 2 and 3 cover an 8 by 9 grid once each.
+    let mut halton = HaltonSampler::new(72);
+    let points: Vec<(f64, f64)> = pixel_samples(&mut halton, (5, 9), 1)
+        .iter()
+        .map(|sample| sample[0])
+        .collect();
+    assert!(stratified(&points, 8, 9));
+}
+
+#[test]
+fn seeds_give_reproducible_and_decorrelated_streams() {
+    for mut sampler in samplers() {
+        let mut stream = |seed: u64| -> Vec<f64> {
+            sampler.set_seed(seed);
+            (0..64)
+                .flat_map(|pixel| pixel_samples(sampler.as_mut(), (pixel, 0), 2))
+                .flatten()
+                .flat_map(|(x, y)| [x, y])
+                .collect()
+        };
+        let first: Vec<f64> = stream(1);
+        let second: Vec<f64> = stream(2);
+        assert_eq!(first, stream(1));
+        assert_ne!(first, second);
+
+        let n: f64 = first.len() as f64;
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / n;
+        let (mean_first, mean_second) = (mean(&first), mean(&second));
+        let covariance: f64 = first
+            .iter()
+            .zip(&second)
+            .map(|(a, b)| (a - mean_first) * (b - mean_second))
+            .sum::<f64>()
+            / n;
+        // Uniform values have a variance of 1 / 12.
+        assert!((covariance * 12.).abs() < 0.05, "{covariance}");
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    color::Color,
+    hittable::HitRecord,
+    material::{Dielectric, Material},
+    ray::Ray,
+    sampler::IndependentSampler,
+    spectrum::{self, RefractiveIndex},
+    vector::{Point, Vector},
+};
+
+/// Colour seen after converting `color` to a spectrum and integrating it back
+/// over stratified wavelengths.
+fn round_trip(color: Color) -> Color {
+    const WAVELENGTHS: usize = 10_000;
+    (0..WAVELENGTHS)
+        .map(|i| {
+            let wavelength: f64 =
+                spectrum::sample_wavelength((i as f64 + 0.5) / WAVELENGTHS as f64);
+            spectrum::spectral_to_rgb(
+                spectrum::rgb_to_spectral(&color, wavelength),
+                wavelength,
+                spectrum::wavelength_pdf(),
+            )
+        })
+        .sum::<Color>()
+        / WAVELENGTHS as f64
+}
+
+#[test]
+fn smits_round_trip_preserves_colours() {
+    let white: Color = round_trip(Color::new(1., 1., 1.));
+    assert!(
+        (white - Color::new(1., 1., 1.)).abs().max() < 0.005,
+        "{white:?}"
+    );
+
+    // Smits' basis spectra are smooth, so saturated primaries bleed a little.
+    for primary in [
+        Color::new(1., 0., 0.),
+        Color::new(0., 1., 0.),
+        Color::new(0., 0., 1.),
+    ] {
+        let result: Color = round_trip(primary);
+        assert!(
+            (result - primary).abs().max() < 0.12,
+            "{primary:?} {result:?}"
+        );
+    }
+}
+
+#[test]
+fn dispersive_glass_bends_blue_more_than_red() {
+    let material: Arc<dyn Material> = Arc::new(Dielectric::dispersive(RefractiveIndex::BK7));
+    let hit_record: HitRecord =
+        HitRecord::new(Point::origin(), Vector::z(), material.clone(), 1., true);
+    let direction: Vector = Vector::new(1., 0., -1.).normalize();
+    let mut sampler = IndependentSampler::new(1);
+
+    // Sine of the angle to the normal after refracting a ray at each wavelength.
+    let mut refracted_sine = |wavelength: f64| -> f64 {
+        let ray: Ray =
+            Ray::new(Point::origin() - direction, direction).with_wavelength(Some(wavelength));
+        loop {
+            let scattering = material.scatter(&ray, &hit_record, &mut sampler).unwrap();
+            let out: Vector = scattering.ray().direction().normalize();
+            if out.z < 0. {
+                return out.x;
+            }
+        }
+    };
+    let blue: f64 = refracted_sine(420.);
+    let red: f64 = refracted_sine(680.);
+    assert!(blue < red - 1e-3, "{blue} {red}");
+
+    // Snell's law with the index at each wavelength.
+    for (wavelength, sine) in [(420., blue), (680., red)] {
+        let index: f64 = RefractiveIndex::BK7.at(Some(wavelength));
+        assert!((sine * index - direction.x).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use ray_tracer::{
+    INFINITY,
+    color::Color,
+    hittable::{Hittable, Sphere},
+    interval::Interval,
+    material::Subsurface,
+    ray::Ray,
+    sampler::{IndependentSampler, Sampler},
+    vector::{Point, Vector},
+};
+
+const WALKS: usize = 2_000;
+const MAX_STEPS: usize = 100_000;
+
+/// Throughput of random walks entering a unit sphere of `albedo`, or `None`
+/// for walks that never left it.
+fn walks(albedo: Color) -> Vec<Option<Color>> {
+    let sphere = Sphere::new(
+        Point::origin(),
+        1.,
+        Arc::new(Subsurface::new(albedo, Color::repeat(0.1), 1.3)),
+    );
+    let mut sampler = IndependentSampler::new(1);
+
+    (0..WALKS)
+        .map(|_| {
+            let (u, v) = sampler.get_2d();
+            let mut ray: Ray = Ray::new(Point::new(u - 0.5, v - 0.5, 3.), Vector::new(0., 0., -1.));
+            let mut throughput: Color = Color::repeat(1.);
+            for _ in 0..MAX_STEPS {
+                let Some(hit_record) = sphere.hit(&ray, Interval::new(1e-6, INFINITY)) else {
+                    return Some(throughput);
+                };
+                let scattering = hit_record
+                    .material()
+                    .scatter(&ray, &hit_record, &mut sampler)?;
+                throughput = throughput.component_mul(&scattering.attenuation());
+                ray = scattering.ray().clone();
+            }
+            None
+        })
+        .collect()
+}
+
+#[test]
+fn non_absorbing_walks_exit_with_all_energy() {
+    for throughput in walks(Color::repeat(1.)) {
+        let throughput: Color = throughput.expect("walk never left the sphere");
+        assert!(
+            (throughput - Color::repeat(1.)).norm() < 1e-9,
+            "{throughput:?}"
+        );
+    }
+}
+
+#[test]
+fn absorption_reduces_throughput() {
+    let mean = |albedo: f64| -> f64 {
+        walks(Color::repeat(albedo))
+            .into_iter()
+            .map(|throughput| throughput.map_or(0., |throughput| throughput.x))
+            .sum::<f64>()
+            / WALKS as f64
+    };
+    let (lossless, light, heavy) = (mean(1.), mean(0.95), mean(0.7));
+    assert!((lossless - 1.).abs() < 1e-9);
+    assert!(light < lossless && heavy < light, "{light} {heavy}");
+    // Fresnel reflection at the entry point survives any absorption.
+    assert!(heavy > 0.02);
+}
@@ -0,0 +1,101 @@
+use nalgebra::Complex;
+use std::sync::Arc;
+
+use ray_tracer::{
+    color::Color,
+    hittable::HitRecord,
+    material::{Dielectric, Material, Metal, ThinFilm},
+    microfacet,
+    ray::Ray,
+    sampler::IndependentSampler,
+    vector::{Point, Vector},
+};
+
+const SAMPLES: usize = 50_000;
+
+/// A ray hitting the origin at `angle` from the normal, travelling down onto a
+/// front face or up onto a back face.
+fn hit(material: Arc<dyn Material>, angle: f64, front_face: bool) -> (Ray, HitRecord) {
+    let down: f64 = if front_face { -1. } else { 1. };
+    let direction: Vector = Vector::new(
+        angle.to_radians().sin(),
+        0.,
+        down * angle.to_radians().cos(),
+    );
+    let hit_record: HitRecord = HitRecord::new(
+        Point::origin(),
+        -down * Vector::z(),
+        material,
+        1.,
+        front_face,
+    );
+    (Ray::new(Point::origin() - direction, direction), hit_record)
+}
+
+#[test]
+fn zero_thickness_over_metal_matches_metal() {
+    let metal: Arc<dyn Material> = Arc::new(Metal::new(Color::new(0.9, 0.6, 0.3), 0.2));
+    let film: Arc<dyn Material> = Arc::new(ThinFilm::new(
+        metal.clone(),
+        0.,
+        1.33,
+        Complex::new(0.2, 3.9),
+    ));
+
+    for angle in [0., 40., 80.] {
+        let (ray, hit_record) = hit(metal.clone(), angle, true);
+        let mut metal_sampler = IndependentSampler::new(1);
+        let mut film_sampler = IndependentSampler::new(1);
+        for _ in 0..1_000 {
+            let expected = metal.scatter(&ray, &hit_record, &mut metal_sampler);
+            let actual = film.scatter(&ray, &hit_record, &mut film_sampler);
+            match (expected, actual) {
+                (Some(expected), Some(actual)) => {
+                    assert_eq!(expected.ray().direction(), actual.ray().direction());
+                    assert!((expected.attenuation() - actual.attenuation()).norm() < 1e-9);
+                }
+                (expected, actual) => assert_eq!(expected.is_some(), actual.is_some()),
+            }
+        }
+    }
+}
+
+#[test]
+fn reflects_the_film_reflectance_over_glass() {
+    let glass: Arc<dyn Material> = Arc::new(Dielectric::new(1.5));
+    let film: Arc<dyn Material> = Arc::new(ThinFilm::new(glass, 300., 1.33, Complex::new(1.5, 0.)));
+
+    // At normal incidence the glass samples its exact Fresnel reflectance, and
+    // a lossless film reflects the same from either side. Capping transmitted
+    // samples at the incident light loses at most the glass reflectance.
+    for front_face in [true, false] {
+        let (ray, hit_record) = hit(film.clone(), 0., front_face);
+        let mut sampler = IndependentSampler::new(1);
+        let (reflected, transmitted) = (0..SAMPLES)
+            .filter_map(|_| film.scatter(&ray, &hit_record, &mut sampler))
+            .fold(
+                (Color::zeros(), Color::zeros()),
+                |(reflected, transmitted), scattering| {
+                    assert!(scattering.attenuation().max() <= 1.);
+                    if scattering.ray().direction().dot(&hit_record.normal()) > 0. {
+                        (reflected + scattering.attenuation(), transmitted)
+                    } else {
+                        (reflected, transmitted + scattering.attenuation())
+                    }
+                },
+            );
+
+        for (channel, wavelength) in [630., 532., 465.].into_iter().enumerate() {
+            let expected: f64 =
+                microfacet::fresnel_thin_film(1., wavelength, 300., 1.33, Complex::new(1.5, 0.));
+            let reflected: f64 = reflected[channel] / SAMPLES as f64;
+            let transmitted: f64 = transmitted[channel] / SAMPLES as f64;
+            assert!(
+                (reflected - expected).abs() < 0.01
+                    && reflected + transmitted < 1.01
+                    && reflected + transmitted > 0.95,
+                "front face {front_face}, {wavelength} nm: {reflected} vs {expected}"
+            );
+        }
+    }
+}